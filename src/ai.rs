@@ -1,208 +1,172 @@
-use crate::lib::Game;
-use crate::lib::GameState;
-use crate::lib::Piece;
-use crate::lib::PieceType;
-use crate::lib::Color;
+use crate::Game;
+use crate::Color;
+use crate::Move;
+use crate::PieceType;
+use crate::PgnTags;
+
+// The piece types that have a value, which the material of each color is counted from
+const VALUED_PIECES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
     
 impl Game {
     // The below code is for a terrible AI, uncommented because everything will change
     fn evaluate_board_state(&mut self) -> (i32, i32) {
         let mut white_value_sum = 0;
         let mut black_value_sum = 0;
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.board[j as usize][i as usize] != None {
-                    {
-                        let currentcolor = self.board[j as usize][i as usize].unwrap().color;
-                        let piecetype = self.board[j as usize][i as usize].unwrap().piecetype;
-                        let value = piecetype.value();
-                        if currentcolor == Color::White {
-                            white_value_sum += value;
-                        } else {
-                            black_value_sum += value;
-                        }
-                        //println!("{:?}", piecetype)
-                    }
-                }
-            }
+        for piecetype in VALUED_PIECES.iter() {
+            let value = piecetype.value();
+            white_value_sum += self.bitboards.pieces(*piecetype, Color::White).count_ones() as i32 * value;
+            black_value_sum += self.bitboards.pieces(*piecetype, Color::Black).count_ones() as i32 * value;
         }
-        //println!("White: {:?}", white_value_sum);
-        //println!("Black: {:?}", black_value_sum);
-        return (white_value_sum, black_value_sum);
+        (white_value_sum, black_value_sum)
+    }
+
+    // The moves the AI chooses between, which are all legal moves but it always promotes to a queen
+    fn ai_get_moves(&self) -> Vec<Move> {
+        let mut all_possible_moves = self.get_all_possible_moves(self.color);
+        all_possible_moves.retain(|possible_move| {
+            possible_move.promotion.is_none() || possible_move.promotion == Some(PieceType::Queen)
+        });
+        all_possible_moves
     }
 
     // The below code is for a terrible AI, uncommented because everything will change
-    fn ai_get_sequential_move(&mut self, i: usize) -> (String, String, Vec<i8>, Vec<i8>) {
-        let owncolor = self.color;
-        let (all_possible_moves, very_useful_map) = self.get_all_possible_moves(&owncolor);
-        //println!("{:?}", owncolor);
+    fn ai_get_sequential_move(&self, i: usize) -> Move {
+        let all_possible_moves = Game::ai_get_moves(self);
         let mut reali = i;
-        //println!("{:?}", all_possible_moves);
-        //println!("{:?}", very_useful_map);
-        /*if all_possible_moves.len() == 0 {
-
-        }*/
         if i >= all_possible_moves.len() {
             reali = all_possible_moves.len() - 1
         }
-        let randommoveto = &all_possible_moves[reali].clone();
-        //println!("{:?}", randommoveto);
-        let randommove = very_useful_map[randommoveto].clone();
-        //println!("{:?}", randommove);
-        let randommovestring = Game::convert_vec_to_string(&vec![randommove.to_vec()])[0].clone();
-        //println!("A random move string: {:?}", randommovestring);
-        let randommovetostring =
-            Game::convert_vec_to_string(&vec![randommoveto.to_vec()])[0].clone();
-        //println!("A random move to string: {:?}", randommovetostring);
-        //println!("All possible: {:?}", very_useful_map);
-        return (
-            randommovestring,
-            randommovetostring,
-            randommove.to_vec(),
-            randommoveto.to_vec(),
-        );
+        all_possible_moves[reali]
+    }
+
+    /* Makes the move without changing the color, looks at the position after it and takes
+    the move back. The moves the player can redo are put back afterwards, since making and
+    taking back moves changes them. A move that can't be made, which is every move once
+    the game is over, leaves the game as it was for the look */
+    fn ai_try_move<T>(&mut self, mv: Move, look: impl FnOnce(&mut Game) -> T) -> T {
+        let redo_stack = std::mem::take(&mut self.redo_stack);
+        let made_move = Game::make_move(self, mv, false).is_ok();
+        let seen = look(self);
+        if made_move {
+            Game::undo_move(self);
+        }
+        self.redo_stack = redo_stack;
+        seen
     }
 
     fn get_data_of_opposite_color(&mut self) -> i32 {
-        let mut owncolor = self.color;
+        let owncolor = self.color;
         let mut best_evaluation = 150;
-        let mut best_move: String = "A2".to_string();
-        let mut best_move_to: String = "A3".to_string();
         let opposite_color = Game::opposite_color_func(owncolor);
-        self.color = opposite_color;
-        let saved_boardstate = self.board;
-        let (all_possible_moves, very_useful_map) = self.get_all_possible_moves(&owncolor);
-        for mut j in 0..all_possible_moves.iter().count() {
-            let saved_boardstate = self.board;
+        Game::change_keyed(self, |game| game.color = opposite_color);
+        let all_possible_moves = Game::ai_get_moves(self);
+        for j in 0..all_possible_moves.len() {
             //let (randommovestring, randommovetostring, randommove, randommoveto) = Game::ai_get_random_move(self);
-            let (randommovestring, randommovetostring, randommove, randommoveto) =
-                Game::ai_get_sequential_move(self, j);
-
-            Game::make_move(
-                self,
-                &randommovestring,
-                randommovetostring.to_string(),
-                false,
-            );
+            let randommove = Game::ai_get_sequential_move(self, j);
 
             // Evals best move
-            let (white_value_sum, black_value_sum) = Game::evaluate_board_state(self);
+            let (white_value_sum, black_value_sum) =
+                Game::ai_try_move(self, randommove, Game::evaluate_board_state);
             if owncolor == Color::White && best_evaluation > black_value_sum {
                 best_evaluation = black_value_sum;
-                best_move = randommovestring;
-                best_move_to = randommovetostring;
             } else if owncolor == Color::Black && best_evaluation > white_value_sum {
                 best_evaluation = white_value_sum;
-                best_move = randommovestring;
-                best_move_to = randommovetostring;
             }
-
-            self.board = saved_boardstate;
         }
-        return best_evaluation;
+        best_evaluation
     }
 
-    fn get_data_of_my_color(&mut self) {
-        let mut owncolor = self.color;
+    /* Returns the move better_chess_ai would make for the current color, without making it,
+    as the game is put back as it was when it's done. It's None if there are no legal moves */
+    pub fn ai_best_move(&mut self) -> Option<Move> {
+        let owncolor = self.color;
         let mut best_evaluation = 150;
-        let mut best_move: String = "A2".to_string();
-        let mut best_move_to: String = "A3".to_string();
-        let opposite_color = Game::opposite_color_func(owncolor);
-        let saved_boardstate = self.board;
-        let (all_possible_moves, very_useful_map) = self.get_all_possible_moves(&owncolor);
+        let mut best_move = None;
+        let all_possible_moves = Game::ai_get_moves(self);
         //let mut best_outcome_for_opposite = 150;
-        let all_equal_moves: Vec<i32> = vec![];
-        for mut j in 0..all_possible_moves.iter().count() {
-            let saved_boardstate = self.board;
+        let _all_equal_moves: Vec<i32> = vec![];
+        for j in 0..all_possible_moves.len() {
             //let (randommovestring, randommovetostring, randommove, randommoveto) = Game::ai_get_random_move(self);
-            let (randommovestring, randommovetostring, randommove, randommoveto) =
-                Game::ai_get_sequential_move(self, j);
-
-            Game::make_move(
-                self,
-                &randommovestring,
-                randommovetostring.to_string(),
-                false,
-            );
+            let randommove = Game::ai_get_sequential_move(self, j);
 
             // Evals best move
-            let best_outcome_for_opposite = Game::get_data_of_opposite_color(self);
-
-            self.color = owncolor;
+            let best_outcome_for_opposite = Game::ai_try_move(self, randommove, |game| {
+                let best_outcome_for_opposite = Game::get_data_of_opposite_color(game);
+                Game::change_keyed(game, |game| game.color = owncolor);
+                best_outcome_for_opposite
+            });
 
             if best_evaluation > best_outcome_for_opposite {
                 best_evaluation = best_outcome_for_opposite;
-                best_move = randommovestring;
-                best_move_to = randommovetostring;
+                best_move = Some(randommove);
             }
-
-            self.board = saved_boardstate;
         }
+        best_move
+    }
+
+    fn get_data_of_my_color(&mut self) {
+        let best_move = Game::ai_best_move(self);
 
         // Makes actual AI move
-        println!("Hellooooo!");
-        //Game::print(self);
-        //println!("testing board: {:?}", self.board);
-        println!("Whose turn: {:?}", owncolor);
-        println!("Best evaulation: {:?}", best_evaluation);
-        //println!("White: {:?}", white_value_sum);
-        //println!("Black: {:?}", black_value_sum);
-        Game::make_move(self, &best_move, best_move_to.to_string(), true);
+        if let Some(best_move) = best_move {
+            if let Err(error) = Game::make_move(self, best_move, true) {
+                println!("{}", error);
+            }
+        }
         Game::print(self);
     }
 
     pub fn better_chess_ai(&mut self) {
-        for mut i in 0..100 {
+        for i in 0..100 {
             Game::get_data_of_my_color(self);
             let (white_value_sum, black_value_sum) = Game::evaluate_board_state(self);
             if white_value_sum < 100 || black_value_sum < 100 {
                 println!("{:?} lost on Turn {:?}", self.color, i);
                 break;
             }
+            if Game::dead_position(self) {
+                println!("Dead position on Turn {:?}, it's a draw", i);
+                break;
+            }
+            if Game::get_game_state(self).is_game_over() {
+                println!("{:?} on Turn {:?}", Game::get_game_state(self), i);
+                break;
+            }
         }
+        println!("{}", Game::to_pgn(self, &PgnTags::default(), &[]));
     }
 
     pub fn chess_ai(&mut self) {
-        for mut i in 0..1000 {
-            let mut owncolor = self.color;
-            let mut checkmate = false;
+        for i in 0..1000 {
+            let owncolor = self.color;
+            let _checkmate = false;
             let mut best_evaluation = 150;
-            let mut best_move: String = "A2".to_string();
-            let mut best_move_to: String = "A3".to_string();
-            let opposite_color = Game::opposite_color_func(owncolor);
-            let saved_boardstate = self.board;
-            let (all_possible_moves, very_useful_map) = self.get_all_possible_moves(&owncolor);
-
-            for j in 0..all_possible_moves.iter().count() {
-                let saved_boardstate = self.board;
+            let mut best_move = None;
+            let _opposite_color = Game::opposite_color_func(owncolor);
+            let all_possible_moves = Game::ai_get_moves(self);
+
+            for j in 0..all_possible_moves.len() {
                 //let (randommovestring, randommovetostring, randommove, randommoveto) = Game::ai_get_random_move(self);
-                let (randommovestring, randommovetostring, randommove, randommoveto) =
-                    Game::ai_get_sequential_move(self, j);
-
-                Game::make_move(
-                    self,
-                    &randommovestring,
-                    randommovetostring.to_string(),
-                    false,
-                );
-
-                let (white_value_sum, black_value_sum) = Game::evaluate_board_state(self);
-                //println!("Current color : {:?}", owncolor);
+                let randommove = Game::ai_get_sequential_move(self, j);
+
+                let (white_value_sum, black_value_sum) =
+                    Game::ai_try_move(self, randommove, Game::evaluate_board_state);
                 if self.color == Color::White && best_evaluation > black_value_sum {
                     best_evaluation = black_value_sum;
-                    best_move = randommovestring;
-                    best_move_to = randommovetostring;
+                    best_move = Some(randommove);
                 } else if self.color == Color::Black && best_evaluation > white_value_sum {
                     best_evaluation = white_value_sum;
-                    best_move = randommovestring;
-                    best_move_to = randommovetostring;
+                    best_move = Some(randommove);
                 }
-
-                self.board = saved_boardstate;
                 //self.color = owncolor;
                 //self.color = opposite_color;
-                //println!("Current color : {:?}", self.color);
-                //Game::print(self);
 
                 /*if Game::check_check(self) {
                     if Game::checkmate(self) {
@@ -216,29 +180,31 @@ impl Game {
                     //self.board = saved_boardstate;
                 }*/
                 //if !checkmate {
-                if j == all_possible_moves.iter().count() - 1 {
-                    println!("Hellooooo!");
-                    Game::print(self);
-                    //println!("testing board: {:?}", self.board);
-                    println!("Whose turn: {:?}", owncolor);
-                    println!("Best evaulation: {:?}", best_evaluation);
-                    println!("White: {:?}", white_value_sum);
-                    println!("Black: {:?}", black_value_sum);
-                    Game::make_move(self, &best_move, best_move_to.to_string(), true);
+                if j == all_possible_moves.len() - 1 {
+                    if let Some(best_move) = best_move {
+                        if let Err(error) = Game::make_move(self, best_move, true) {
+                            println!("{}", error);
+                        }
+                    }
                     Game::print(self);
                 }
             }
             let (white_value_sum, black_value_sum) = Game::evaluate_board_state(self);
-            //println!({"{:?} to {:?}"}, best_move, best_move_to);
-            //println!("{:?} {:?}", {"{:?}"}, &best_move);
-            //println!("{:?} {:?}", {"{:?}"}, &best_move_to);
-            //let hmm = best_move.clone();
 
             if white_value_sum < 100 || black_value_sum < 100 {
                 println!("{:?} lost on Turn {:?}", self.color, i);
                 break;
             }
+            if Game::dead_position(self) {
+                println!("Dead position on Turn {:?}, it's a draw", i);
+                break;
+            }
+            if Game::get_game_state(self).is_game_over() {
+                println!("{:?} on Turn {:?}", Game::get_game_state(self), i);
+                break;
+            }
         }
+        println!("{}", Game::to_pgn(self, &PgnTags::default(), &[]));
         //Game::min_max(self);
     }
 }
\ No newline at end of file
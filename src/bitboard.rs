@@ -0,0 +1,330 @@
+use crate::Board;
+use crate::Color;
+use crate::Game;
+use crate::Move;
+use crate::Piece;
+use crate::PieceType;
+use crate::Square;
+use crate::KING_STEPS;
+use crate::KNIGHT_STEPS;
+use crate::zobrist;
+
+/* The squares a piece standing on a square attacks, as bitboards where a1 is bit 0, b1
+bit 1 and so on up to h8 at bit 63. The pawn tables are for white and black pawns */
+const KNIGHT_ATTACKS: [u64; 64] = step_attacks(&KNIGHT_STEPS);
+const KING_ATTACKS: [u64; 64] = step_attacks(&KING_STEPS);
+const PAWN_ATTACKS: [[u64; 64]; 2] = [
+    step_attacks(&[(-1, 1), (1, 1)]),
+    step_attacks(&[(-1, -1), (1, -1)]),
+];
+
+/* The squares from a square to the edge of the board in each of the directions of
+KING_STEPS, not counting the square itself, which sliding pieces attack up to the
+first piece in the way */
+const RAYS: [[u64; 64]; 8] = rays();
+
+// The directions of KING_STEPS that rooks and bishops slide in
+const ROOK_DIRECTIONS: [usize; 4] = [2, 3, 4, 5];
+const BISHOP_DIRECTIONS: [usize; 4] = [0, 1, 6, 7];
+
+// Makes the table of the squares that are one of the steps away from every square
+const fn step_attacks(steps: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let mut step = 0;
+        while step < steps.len() {
+            let file = (index % 8) as i8 + steps[step].0;
+            let rank = (index / 8) as i8 + steps[step].1;
+            if file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                table[index] |= 1 << (rank * 8 + file);
+            }
+            step += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+const fn rays() -> [[u64; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let (files, ranks) = KING_STEPS[direction];
+        let mut index = 0;
+        while index < 64 {
+            let mut file = (index % 8) as i8 + files;
+            let mut rank = (index / 8) as i8 + ranks;
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                table[direction][index] |= 1 << (rank * 8 + file);
+                file += files;
+                rank += ranks;
+            }
+            index += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+/* Returns the first of the pieces on a ray in the direction, which is the one closest
+to the square the ray starts from: the lowest bit for directions going up the board
+(or right along the rank) and the highest bit for the others */
+fn nearest(direction: usize, blockers: u64) -> Option<usize> {
+    if blockers == 0 {
+        return None;
+    }
+    let (files, ranks) = KING_STEPS[direction];
+    Some(if ranks > 0 || (ranks == 0 && files > 0) {
+        blockers.trailing_zeros() as usize
+    } else {
+        63 - blockers.leading_zeros() as usize
+    })
+}
+
+/* Returns the squares a piece sliding in the directions from the square attacks, where
+each ray is cut off behind the first piece on it */
+fn sliding_attacks(square: Square, occupied: u64, directions: &[usize]) -> u64 {
+    let mut attacks = 0;
+    for direction in directions {
+        let ray = RAYS[*direction][square.index()];
+        attacks |= ray;
+        if let Some(blocker) = nearest(*direction, ray & occupied) {
+            attacks &= !RAYS[*direction][blocker];
+        }
+    }
+    attacks
+}
+
+// Returns the squares in a bitboard, from a1 upwards
+pub fn squares(mut bitboard: u64) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let index = bitboard.trailing_zeros() as u8;
+        bitboard &= bitboard - 1;
+        Square::from_index(index)
+    })
+}
+
+// Every piece type, in the order of the bitboards, which is the order they're declared in
+const PIECE_TYPES: [PieceType; 7] = [
+    PieceType::Pawn,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Corpse,
+];
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/* The pieces on the board, as one bitboard for every piece type and one for every color.
+The piece on a square is the piece type whose bitboard has the square, in the color
+whose bitboard has it. The Zobrist key of the pieces is kept along with them */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Bitboards {
+    pieces: [u64; 7],
+    colors: [u64; 2],
+    key: u64,
+}
+
+impl Bitboards {
+    pub fn empty() -> Bitboards {
+        Bitboards {
+            pieces: [0; 7],
+            colors: [0; 2],
+            key: 0,
+        }
+    }
+
+    pub fn from_board(board: &Board) -> Bitboards {
+        let mut bitboards = Bitboards::empty();
+        for square in Square::all() {
+            bitboards.set_piece(square, board[square.row()][square.column()]);
+        }
+        bitboards
+    }
+
+    // Returns the board as rows from the eighth rank down, with the a-file first in each row
+    pub fn to_board(&self) -> Board {
+        let mut board = [[None; 8]; 8];
+        for square in Square::all() {
+            board[square.row()][square.column()] = self.piece_at(square);
+        }
+        board
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        let bit = 1 << square.index();
+        let color = if self.colors[0] & bit != 0 {
+            Color::White
+        } else if self.colors[1] & bit != 0 {
+            Color::Black
+        } else {
+            return None;
+        };
+        let piecetype = PIECE_TYPES
+            .iter()
+            .find(|piecetype| self.pieces[**piecetype as usize] & bit != 0)?;
+        Some(Piece {
+            piecetype: *piecetype,
+            color,
+        })
+    }
+
+    // Puts a piece on the square, or empties it with None
+    pub fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
+        let bit = 1 << square.index();
+        if let Some(removed) = self.piece_at(square) {
+            self.key ^= zobrist::piece_key(removed, square);
+        }
+        for bitboard in self.pieces.iter_mut().chain(self.colors.iter_mut()) {
+            *bitboard &= !bit;
+        }
+        if let Some(piece) = piece {
+            self.pieces[piece.piecetype as usize] |= bit;
+            self.colors[color_index(piece.color)] |= bit;
+            self.key ^= zobrist::piece_key(piece, square);
+        }
+    }
+
+    // Returns the Zobrist key of the pieces, without the color to move and the rights
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    // Returns the squares with a piece of the type and color on them
+    pub fn pieces(&self, piecetype: PieceType, color: Color) -> u64 {
+        self.pieces[piecetype as usize] & self.colors[color_index(color)]
+    }
+
+    // Returns the squares with a piece of the color on them
+    pub fn color(&self, color: Color) -> u64 {
+        self.colors[color_index(color)]
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+
+    // Returns the square of the king of the color, if it has one
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        squares(self.pieces(PieceType::King, color)).next()
+    }
+
+    /* Returns the squares the piece on the square attacks, whether they're empty or have
+    a piece of either color on them. Pawns attack the two squares diagonally forward */
+    pub fn attacks(&self, square: Square) -> u64 {
+        let piece = match self.piece_at(square) {
+            Some(piece) => piece,
+            None => return 0,
+        };
+        let occupied = self.occupied();
+        match piece.piecetype {
+            PieceType::Pawn => PAWN_ATTACKS[color_index(piece.color)][square.index()],
+            PieceType::Knight => KNIGHT_ATTACKS[square.index()],
+            PieceType::King => KING_ATTACKS[square.index()],
+            PieceType::Bishop => sliding_attacks(square, occupied, &BISHOP_DIRECTIONS),
+            PieceType::Rook => sliding_attacks(square, occupied, &ROOK_DIRECTIONS),
+            PieceType::Queen => {
+                sliding_attacks(square, occupied, &BISHOP_DIRECTIONS)
+                    | sliding_attacks(square, occupied, &ROOK_DIRECTIONS)
+            }
+            PieceType::Corpse => 0,
+        }
+    }
+
+    /* Returns the squares of the pieces of the color that attack the square, by looking
+    outwards from the square with the attacks of every piece type, since a piece on the
+    square would attack exactly the squares those pieces attack it from */
+    pub fn attackers(&self, square: Square, by_color: Color) -> u64 {
+        let index = square.index();
+        let occupied = self.occupied();
+        let queens = self.pieces(PieceType::Queen, by_color);
+        let opposite_side = 1 - color_index(by_color);
+        (PAWN_ATTACKS[opposite_side][index] & self.pieces(PieceType::Pawn, by_color))
+            | (KNIGHT_ATTACKS[index] & self.pieces(PieceType::Knight, by_color))
+            | (KING_ATTACKS[index] & self.pieces(PieceType::King, by_color))
+            | (sliding_attacks(square, occupied, &BISHOP_DIRECTIONS)
+                & (self.pieces(PieceType::Bishop, by_color) | queens))
+            | (sliding_attacks(square, occupied, &ROOK_DIRECTIONS)
+                & (self.pieces(PieceType::Rook, by_color) | queens))
+    }
+
+    pub fn is_attacked(&self, square: Square, by_color: Color) -> bool {
+        self.attackers(square, by_color) != 0
+    }
+
+    /* Returns the squares of the pieces of the color that can't leave the line between
+    their king and an opposite rook, bishop or queen, which would attack the king if they
+    did. Each line from the king is followed to its first two pieces, and the first is
+    pinned if it's of the color and the second is a piece that slides along that line */
+    pub fn pinned(&self, color: Color) -> u64 {
+        let king = match self.king_square(color) {
+            Some(king) => king,
+            None => return 0,
+        };
+        let occupied = self.occupied();
+        let opposite_color = Game::opposite_color_func(color);
+        let queens = self.pieces(PieceType::Queen, opposite_color);
+        let rooks = self.pieces(PieceType::Rook, opposite_color) | queens;
+        let bishops = self.pieces(PieceType::Bishop, opposite_color) | queens;
+        let lines = ROOK_DIRECTIONS
+            .iter()
+            .map(|direction| (*direction, rooks))
+            .chain(BISHOP_DIRECTIONS.iter().map(|direction| (*direction, bishops)));
+
+        let mut pinned = 0;
+        for (direction, sliders) in lines {
+            let blockers = RAYS[direction][king.index()] & occupied;
+            let first = match nearest(direction, blockers) {
+                Some(first) => first,
+                None => continue,
+            };
+            if let Some(second) = nearest(direction, blockers & !(1 << first)) {
+                if self.color(color) & (1 << first) != 0 && sliders & (1 << second) != 0 {
+                    pinned |= 1 << first;
+                }
+            }
+        }
+        pinned
+    }
+
+    /* Moves the piece, including the extra piece that en passant and castling affect,
+    which is told by the flags of the move. A promoted pawn is replaced by a piece of
+    the promotion type. Only the game makes moves here, as there has to be a piece to move */
+    pub(crate) fn make_move(&mut self, mv: Move) {
+        let own_piece = self.piece_at(mv.from).unwrap();
+
+        // A pawn moving onto the en passant square captures the pawn that passed it
+        if mv.flags.en_passant {
+            self.set_piece(Square::new(mv.to.file(), mv.from.rank()).unwrap(), None);
+        }
+
+        // Castling is the only move where the king goes two steps, and it brings the rook along
+        if mv.flags.castling {
+            let (rook_from, rook_to) = if mv.to.file() == 6 { (7, 5) } else { (0, 3) };
+            let rook_from = Square::new(rook_from, mv.from.rank()).unwrap();
+            let rook_to = Square::new(rook_to, mv.from.rank()).unwrap();
+            self.set_piece(rook_to, self.piece_at(rook_from));
+            self.set_piece(rook_from, None);
+        }
+
+        self.set_piece(mv.from, None);
+        self.set_piece(
+            mv.to,
+            Some(Piece {
+                piecetype: mv.promotion.unwrap_or(own_piece.piecetype),
+                color: own_piece.color,
+            }),
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::FenError;
+use crate::Game;
+use crate::Move;
+use crate::SanError;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/* A position of a test suite in Extended Position Description, which is the first four
+fields of a FEN followed by operations like bm Qd1+; id "WAC.001"; */
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdRecord {
+    // The placement, color, castling and en passant fields of the FEN
+    pub fen: String,
+    pub id: Option<String>,
+    // bm, the moves that solve the position
+    pub best_moves: Vec<Move>,
+    // am, the moves that must be avoided
+    pub avoid_moves: Vec<Move>,
+    // ce, the evaluation in hundredths of a pawn for the color to move
+    pub centipawns: Option<i32>,
+    // acd, how deep the evaluation was analysed
+    pub depth: Option<u32>,
+    // Any other operations, with their operands as they were written
+    pub operations: Vec<(String, String)>,
+}
+
+// What is wrong with an EPD record
+#[derive(Clone, Debug, PartialEq)]
+pub enum EpdError {
+    BadFen(FenError),
+    // An operation that can't be read, e.g. one with a string that is never closed
+    BadOperation(String),
+    // A move of bm or am that isn't legal in the position
+    BadMove { opcode: String, error: SanError },
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::BadFen(error) => write!(f, "{}", error),
+            EpdError::BadOperation(operation) => {
+                write!(f, "Can't read the operation \"{}\"", operation)
+            }
+            EpdError::BadMove { opcode, error } => write!(f, "{} in {}", error, opcode),
+        }
+    }
+}
+
+impl Error for EpdError {}
+
+impl EpdRecord {
+    // Sets up a game from the position, with the halfmove clock at 0 and the fullmove number at 1
+    pub fn to_game(&self) -> Result<Game, FenError> {
+        Game::from_fen(&self.fen)
+    }
+
+    // Reads every record of a test suite with one on each line, where empty lines are skipped
+    pub fn read_all(epd: &str) -> Result<Vec<EpdRecord>, EpdError> {
+        epd.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.parse())
+            .collect()
+    }
+}
+
+// Splits the operations at the semicolons that end them, which can also be inside strings
+fn split_operations(text: &str) -> Result<Vec<String>, EpdError> {
+    let mut operations = vec![];
+    let mut operation = String::new();
+    let mut in_string = false;
+    for letter in text.chars() {
+        match letter {
+            '"' => in_string = !in_string,
+            ';' if !in_string => {
+                operations.push(operation.trim().to_string());
+                operation.clear();
+                continue;
+            }
+            _ => {}
+        }
+        operation.push(letter);
+    }
+    if in_string {
+        return Err(EpdError::BadOperation(operation.trim().to_string()));
+    }
+    // The last operation is also read if its semicolon was left out
+    if !operation.trim().is_empty() {
+        operations.push(operation.trim().to_string());
+    }
+    Ok(operations)
+}
+
+impl FromStr for EpdRecord {
+    type Err = EpdError;
+
+    fn from_str(s: &str) -> Result<EpdRecord, EpdError> {
+        let mut rest = s.trim();
+        let mut fields = vec![];
+        for _ in 0..4 {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        let fen = fields.join(" ");
+        let game = Game::from_fen(&fen).map_err(EpdError::BadFen)?;
+
+        let mut record = EpdRecord {
+            fen,
+            id: None,
+            best_moves: vec![],
+            avoid_moves: vec![],
+            centipawns: None,
+            depth: None,
+            operations: vec![],
+        };
+        for operation in split_operations(rest)? {
+            let bad_operation = || EpdError::BadOperation(operation.clone());
+            let end = operation.find(char::is_whitespace).unwrap_or(operation.len());
+            let (opcode, operands) = (&operation[..end], operation[end..].trim());
+            match opcode {
+                "bm" | "am" => {
+                    let moves = operands
+                        .split_whitespace()
+                        .map(|san| Game::parse_san(&game, san))
+                        .collect::<Result<Vec<Move>, SanError>>()
+                        .map_err(|error| EpdError::BadMove { opcode: opcode.to_string(), error })?;
+                    if opcode == "bm" {
+                        record.best_moves = moves;
+                    } else {
+                        record.avoid_moves = moves;
+                    }
+                }
+                "id" => {
+                    let id = operands.strip_prefix('"').and_then(|id| id.strip_suffix('"'));
+                    record.id = Some(id.unwrap_or(operands).to_string());
+                }
+                "ce" => record.centipawns = Some(operands.parse().map_err(|_| bad_operation())?),
+                "acd" => record.depth = Some(operands.parse().map_err(|_| bad_operation())?),
+                "" => return Err(bad_operation()),
+                _ => record.operations.push((opcode.to_string(), operands.to_string())),
+            }
+        }
+        Ok(record)
+    }
+}
+
+/* Writes the record on one line, with the moves of bm and am in SAN, and the id last
+like most test suites have it */
+impl fmt::Display for EpdRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.fen)?;
+        // Moves are written in UCI if they aren't legal, which they are when the record was read
+        let game = self.to_game().ok();
+        for (opcode, moves) in [("bm", &self.best_moves), ("am", &self.avoid_moves)].iter() {
+            if !moves.is_empty() {
+                let sans: Vec<String> = moves
+                    .iter()
+                    .map(|mv| match game.as_ref().map(|game| Game::to_san(game, *mv)) {
+                        Some(Ok(san)) => san,
+                        _ => mv.to_string(),
+                    })
+                    .collect();
+                write!(f, " {} {};", opcode, sans.join(" "))?;
+            }
+        }
+        if let Some(centipawns) = self.centipawns {
+            write!(f, " ce {};", centipawns)?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " acd {};", depth)?;
+        }
+        for (opcode, operands) in self.operations.iter() {
+            write!(f, " {} {};", opcode, operands)?;
+        }
+        if let Some(id) = &self.id {
+            write!(f, " id \"{}\";", id)?;
+        }
+        Ok(())
+    }
+}
+
+// How the AI did on a test suite
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EpdReport {
+    // Every record that has a bm or am, with its id, the move the AI chose and if it was right
+    pub results: Vec<(Option<String>, Option<Move>, bool)>,
+}
+
+impl EpdReport {
+    pub fn solved(&self) -> usize {
+        self.results.iter().filter(|(_, _, solved)| *solved).count()
+    }
+
+    // The part of the records that were solved, from 0 to 100
+    pub fn solved_percentage(&self) -> f64 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        100.0 * self.solved() as f64 / self.results.len() as f64
+    }
+}
+
+impl Game {
+    /* Asks the AI for a move in every position of a test suite. A position is solved if
+    the move is one of its best moves and none of the moves to avoid, and records
+    without either are left out */
+    pub fn run_epd_suite(records: &[EpdRecord]) -> EpdReport {
+        let same_move =
+            |a: &Move, b: &Move| (a.from, a.to, a.promotion) == (b.from, b.to, b.promotion);
+        let mut report = EpdReport::default();
+        for record in records {
+            if record.best_moves.is_empty() && record.avoid_moves.is_empty() {
+                continue;
+            }
+            let ai_move = record.to_game().ok().and_then(|mut game| Game::ai_best_move(&mut game));
+            let solved = match &ai_move {
+                Some(mv) => {
+                    let best = record.best_moves.iter().any(|best| same_move(best, mv));
+                    let avoided = record.avoid_moves.iter().any(|avoid| same_move(avoid, mv));
+                    (best || record.best_moves.is_empty()) && !avoided
+                }
+                None => false,
+            };
+            report.results.push((record.id.clone(), ai_move, solved));
+        }
+        report
+    }
+}
@@ -0,0 +1,228 @@
+use crate::Bitboards;
+use crate::CastlingRights;
+use crate::Color;
+use crate::Game;
+use crate::Piece;
+use crate::PieceType;
+use crate::Square;
+use std::error::Error;
+use std::fmt;
+
+// The position at the start of a game
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// What is wrong with a FEN string, where ranks are numbered like on the board (1 to 8)
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    // A FEN has six fields separated by spaces, of which the last two can be left out
+    WrongNumberOfFields(usize),
+    WrongNumberOfRanks(usize),
+    InvalidPiece { rank: u8, letter: char },
+    // The rank doesn't add up to eight squares
+    WrongRankLength { rank: u8, length: usize },
+    InvalidColor(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongNumberOfFields(count) => {
+                write!(f, "A FEN has 6 fields (or 4 without the clocks), not {}", count)
+            }
+            FenError::WrongNumberOfRanks(count) => {
+                write!(f, "The board has 8 ranks separated by '/', not {}", count)
+            }
+            FenError::InvalidPiece { rank, letter } => {
+                write!(f, "'{}' on rank {} is not a piece", letter, rank)
+            }
+            FenError::WrongRankLength { rank, length } => {
+                write!(f, "Rank {} has {} squares instead of 8", rank, length)
+            }
+            FenError::InvalidColor(field) => {
+                write!(f, "The color to move is 'w' or 'b', not \"{}\"", field)
+            }
+            FenError::InvalidCastling(field) => {
+                write!(f, "The castling rights are '-' or some of \"KQkq\", not \"{}\"", field)
+            }
+            FenError::InvalidEnPassant(field) => write!(
+                f,
+                "The en passant square is '-' or a square on rank 3 or 6, not \"{}\"",
+                field
+            ),
+            FenError::InvalidHalfmoveClock(field) => {
+                write!(f, "The halfmove clock is a number, not \"{}\"", field)
+            }
+            FenError::InvalidFullmoveNumber(field) => {
+                write!(f, "The fullmove number is a number from 1, not \"{}\"", field)
+            }
+        }
+    }
+}
+
+impl Error for FenError {}
+
+impl Game {
+    /* Sets up a game from a position in Forsyth-Edwards Notation, e.g. START_FEN.
+    The halfmove clock and fullmove number can be left out, and are then 0 and 1 */
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WrongNumberOfFields(fields.len()));
+        }
+
+        let mut game = Game::new();
+        game.bitboards = Bitboards::from_board(&Game::parse_placement(fields[0])?);
+        game.color = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => return Err(FenError::InvalidColor(other.to_string())),
+        };
+        game.castling_rights = Game::parse_castling(fields[2])?;
+
+        // The en passant square is behind the pawn that just made a double step
+        game.en_passant = match fields[3] {
+            "-" => None,
+            field => {
+                let square: Square = field
+                    .parse()
+                    .map_err(|_| FenError::InvalidEnPassant(field.to_string()))?;
+                let rank = if game.color == Color::White { 5 } else { 2 };
+                if square.rank() != rank {
+                    return Err(FenError::InvalidEnPassant(field.to_string()));
+                }
+                Some(square)
+            }
+        };
+
+        if fields.len() == 6 {
+            game.halfmove_clock = fields[4]
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(fields[4].to_string()))?;
+            game.fullmove_number = match fields[5].parse() {
+                Ok(number) if number >= 1 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber(fields[5].to_string())),
+            };
+        }
+
+        game.refresh_zobrist_key();
+        game.history = vec![game.get_position()];
+        Game::update_game_state(&mut game);
+        Ok(game)
+    }
+
+    // Reads the first field, which lists the ranks from 8 to 1 and each rank from the a-file
+    fn parse_placement(placement: &str) -> Result<[[Option<Piece>; 8]; 8], FenError> {
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongNumberOfRanks(ranks.len()));
+        }
+
+        let mut board = [[None; 8]; 8];
+        for (row, rank_text) in ranks.iter().enumerate() {
+            let rank = 8 - row as u8;
+            let mut length = 0;
+            for letter in rank_text.chars() {
+                if let Some(empty) = letter.to_digit(10).filter(|empty| (1..=8).contains(empty)) {
+                    length += empty as usize;
+                    continue;
+                }
+                let piecetype = PieceType::from_letter(letter)
+                    .ok_or(FenError::InvalidPiece { rank, letter })?;
+                if length < 8 {
+                    board[row][length] = Some(Piece {
+                        piecetype,
+                        color: if letter.is_ascii_uppercase() { Color::White } else { Color::Black },
+                    });
+                }
+                length += 1;
+            }
+            if length != 8 {
+                return Err(FenError::WrongRankLength { rank, length });
+            }
+        }
+        Ok(board)
+    }
+
+    fn parse_castling(field: &str) -> Result<CastlingRights, FenError> {
+        let mut castling_rights = CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        };
+        if field == "-" {
+            return Ok(castling_rights);
+        }
+        for letter in field.chars() {
+            let right = match letter {
+                'K' => &mut castling_rights.white_kingside,
+                'Q' => &mut castling_rights.white_queenside,
+                'k' => &mut castling_rights.black_kingside,
+                'q' => &mut castling_rights.black_queenside,
+                _ => return Err(FenError::InvalidCastling(field.to_string())),
+            };
+            // Every letter can only be there once
+            if *right {
+                return Err(FenError::InvalidCastling(field.to_string()));
+            }
+            *right = true;
+        }
+        Ok(castling_rights)
+    }
+
+    // Writes the game as a FEN string with all six fields
+    pub fn to_fen(&self) -> String {
+        let mut ranks = vec![];
+        for row in self.board().iter() {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for square in row.iter() {
+                match square {
+                    Some(piece) if piece.piecetype != PieceType::Corpse => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = piece.piecetype.letter();
+                        rank.push(if piece.color == Color::White {
+                            letter
+                        } else {
+                            letter.to_ascii_lowercase()
+                        });
+                    }
+                    _ => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            ranks.push(rank);
+        }
+
+        let rights = self.castling_rights;
+        let castling: String = [
+            (rights.white_kingside, 'K'),
+            (rights.white_queenside, 'Q'),
+            (rights.black_kingside, 'k'),
+            (rights.black_queenside, 'q'),
+        ]
+        .iter()
+        .filter(|(right, _)| *right)
+        .map(|(_, letter)| *letter)
+        .collect();
+
+        format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            if self.color == Color::White { "w" } else { "b" },
+            if castling.is_empty() { "-".to_string() } else { castling },
+            self.en_passant.map_or("-".to_string(), |square| square.to_string()),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+}
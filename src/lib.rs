@@ -1,30 +1,103 @@
 // Import modules
 //use rand::seq::SliceRandom;
-use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
 use std::io;
+use std::str::FromStr;
 use std::io::prelude::*;
 
+mod ai;
+mod bitboard;
+mod epd;
+mod fen;
+mod perft;
+mod pgn;
+mod san;
+mod uci;
+mod zobrist;
+mod test;
+
+pub use bitboard::Bitboards;
+pub use epd::{EpdError, EpdRecord, EpdReport};
+pub use fen::{FenError, START_FEN};
+pub use pgn::{PgnAnnotation, PgnError, PgnErrorKind, PgnGame, PgnMove, PgnTags};
+pub use san::SanError;
+pub use uci::NULL_MOVE_UCI;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum GameState {
     InProgress,
     Check,
-    GameOver,
+    GameOver(GameResult),
+}
+
+// How a finished game ended, and who won it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    Checkmate { winner: Color },
+    Resignation { winner: Color },
+    Timeout { winner: Color },
+    Draw(DrawReason),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DrawReason {
+    Stalemate,
+    DeadPosition,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    Agreement,
+}
+
+impl GameState {
+    pub fn is_game_over(&self) -> bool {
+        matches!(self, GameState::GameOver(_))
+    }
+
+    // Returns the result as written in chess notation, which is "*" while the game goes on
+    pub fn result_string(&self) -> &'static str {
+        match self {
+            GameState::GameOver(result) => result.result_string(),
+            _ => "*",
+        }
+    }
+}
+
+impl GameResult {
+    pub fn winner(&self) -> Option<Color> {
+        match *self {
+            GameResult::Checkmate { winner }
+            | GameResult::Resignation { winner }
+            | GameResult::Timeout { winner } => Some(winner),
+            GameResult::Draw(_) => None,
+        }
+    }
+
+    // Returns "1-0" if white won, "0-1" if black won and "1/2-1/2" for a draw
+    pub fn result_string(&self) -> &'static str {
+        match self.winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub piecetype: PieceType,
     pub color: Color,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PieceType {
     Pawn,
     Rook,
@@ -35,6 +108,327 @@ pub enum PieceType {
     Corpse,
 }
 
+/* A square on the board, made from a file (0 is the a-file) and a rank (0 is the
+first rank, where white starts), which can't be made outside the board */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Square {
+    file: u8,
+    rank: u8,
+}
+
+impl Square {
+    // Returns the square on the given file and rank, if it's on the board
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if file < 8 && rank < 8 {
+            Some(Square { file, rank })
+        } else {
+            None
+        }
+    }
+
+    pub fn file(&self) -> u8 {
+        self.file
+    }
+
+    pub fn rank(&self) -> u8 {
+        self.rank
+    }
+
+    // Returns the square the given number of files and ranks away, if it's on the board
+    pub fn offset(&self, files: i8, ranks: i8) -> Option<Square> {
+        let file = self.file as i8 + files;
+        let rank = self.rank as i8 + ranks;
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            Square::new(file as u8, rank as u8)
+        } else {
+            None
+        }
+    }
+
+    // Returns all 64 squares, from a1 to h1 and up to h8
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(|index| Square {
+            file: index % 8,
+            rank: index / 8,
+        })
+    }
+
+    // The bit of the square in a bitboard, where a1 is 0, b1 is 1 and h8 is 63
+    fn index(&self) -> usize {
+        (self.rank * 8 + self.file) as usize
+    }
+
+    fn from_index(index: u8) -> Option<Square> {
+        Square::new(index % 8, index / 8)
+    }
+
+    // The row and column of the square in Game::board(), where row 0 is the eighth rank
+    fn row(&self) -> usize {
+        7 - self.rank as usize
+    }
+
+    fn column(&self) -> usize {
+        self.file as usize
+    }
+}
+
+// Writes the square like "e4"
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file) as char, self.rank + 1)
+    }
+}
+
+// Reads a square written like "e4" or "E4"
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(s: &str) -> Result<Square, ParseSquareError> {
+        let error = || ParseSquareError {
+            input: s.to_string(),
+        };
+        let mut chars = s.chars();
+        let (file, rank) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(file), Some(rank), None) => (file.to_ascii_lowercase(), rank),
+            _ => return Err(error()),
+        };
+        if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return Err(error());
+        }
+        Square::new(file as u8 - b'a', rank as u8 - b'1').ok_or_else(error)
+    }
+}
+
+// The text that couldn't be read as a square
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseSquareError {
+    pub input: String,
+}
+
+impl fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" is not a square", self.input)
+    }
+}
+
+impl Error for ParseSquareError {}
+
+/* A move of the piece on from to to, with the piece a pawn is promoted to when it
+reaches the last rank. The flags are filled in by the moves Game generates, and
+don't matter when a move is given to make_move */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+    pub flags: MoveFlags,
+}
+
+/* Reads a move written as two squares, where the last one can be followed by
+the piece to promote to, e.g. "e2 e4", "e2e4" or "a7 a8q" */
+impl FromStr for Move {
+    type Err = MoveError;
+
+    fn from_str(s: &str) -> Result<Move, MoveError> {
+        let error = || MoveError::BadNotation(s.to_string());
+        let squares: String = s.split_whitespace().collect();
+        let from = squares.get(0..2).ok_or_else(error)?.parse().map_err(|_| error())?;
+        let to = squares.get(2..4).ok_or_else(error)?.parse().map_err(|_| error())?;
+        let promotion_letter = squares.get(4..).ok_or_else(error)?.to_uppercase();
+        if promotion_letter.is_empty() {
+            return Ok(Move::new(from, to));
+        }
+        PROMOTION_PIECES
+            .iter()
+            .find(|(letter, _)| *letter == promotion_letter)
+            .map(|(_, piecetype)| Move::with_promotion(from, to, *piecetype))
+            .ok_or_else(error)
+    }
+}
+
+// Writes the move in UCI long algebraic notation, e.g. "e2e4" or "e7e8q"
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "{}", promotion.letter().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+// What a move that was made did
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveOutcome {
+    // The move as it was made, with its flags filled in
+    pub mv: Move,
+    pub captured: Option<Piece>,
+    pub promotion: Option<PieceType>,
+    // If the move attacks the other color's king
+    pub check: bool,
+    pub state: GameState,
+}
+
+impl MoveOutcome {
+    // Returns the result of the game if the move ended it
+    pub fn game_over(&self) -> Option<GameResult> {
+        match self.state {
+            GameState::GameOver(result) => Some(result),
+            _ => None,
+        }
+    }
+}
+
+// Why a move couldn't be made
+#[derive(Clone, Debug, PartialEq)]
+pub enum MoveError {
+    // The text couldn't be read as a move
+    BadNotation(String),
+    EmptySquare(Square),
+    // The piece belongs to the other color, where the color is the one whose turn it is
+    WrongSide(Color),
+    // The piece can't move like that
+    IllegalMove,
+    // The piece could get there if there wasn't another piece in the way
+    BlockedPath,
+    LeavesKingInCheck,
+    // A pawn reaching the last rank wasn't given a piece to promote to, or another move was
+    InvalidPromotion,
+    GameOver,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::BadNotation(input) => write!(f, "\"{}\" is not a move", input),
+            MoveError::EmptySquare(square) => write!(f, "There is no piece on {}", square),
+            MoveError::WrongSide(color) => write!(f, "It's {:?}'s turn, you know", color),
+            MoveError::IllegalMove => write!(f, "That piece can't move there"),
+            MoveError::BlockedPath => write!(f, "There is a piece in the way"),
+            MoveError::LeavesKingInCheck => write!(f, "That would leave the king in check"),
+            MoveError::InvalidPromotion => {
+                write!(f, "Only a pawn reaching the last rank is promoted, and it has to be")
+            }
+            MoveError::GameOver => write!(f, "The game is already over"),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+// Something that happened in the game, which is sent to every GameObserver
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    MoveMade(Move),
+    // The piece that was captured, and the square the capturing piece moved to
+    Capture { square: Square, piece: Piece },
+    Promotion { square: Square, piecetype: PieceType },
+    // The color whose king is in check
+    Check(Color),
+    GameOver(GameResult),
+}
+
+/* Gets the events of a game it has subscribed to, which is how the game tells
+the outside what happens instead of printing it */
+pub trait GameObserver: Send + Sync {
+    fn on_event(&mut self, event: &GameEvent);
+}
+
+// Prints the events in the terminal, which play_the_game uses
+struct TerminalPrinter;
+
+impl GameObserver for TerminalPrinter {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::MoveMade(_) => {}
+            GameEvent::Capture { square, piece } => {
+                println!("{:?}'s {:?} on {} is captured", piece.color, piece.piecetype, square)
+            }
+            GameEvent::Promotion { square, piecetype } => {
+                println!("The pawn on {} is promoted to a {:?}", square, piecetype)
+            }
+            GameEvent::Check(color) => println!("Check! {:?}'s king is attacked", color),
+            GameEvent::GameOver(result) => println!("{:?}! {}", result, result.result_string()),
+        }
+    }
+}
+
+// What else a move does than moving a piece
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MoveFlags {
+    pub capture: bool,
+    pub en_passant: bool,
+    pub castling: bool,
+    pub double_pawn_push: bool,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+            flags: MoveFlags::default(),
+        }
+    }
+
+    pub fn with_promotion(from: Square, to: Square, promotion: PieceType) -> Move {
+        Move {
+            promotion: Some(promotion),
+            ..Move::new(from, to)
+        }
+    }
+}
+
+// Which sides each player may still castle to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    pub fn all() -> CastlingRights {
+        CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        }
+    }
+
+    // Returns the (kingside, queenside) rights of the given color
+    pub fn for_color(&self, color: Color) -> (bool, bool) {
+        if color == Color::White {
+            (self.white_kingside, self.white_queenside)
+        } else {
+            (self.black_kingside, self.black_queenside)
+        }
+    }
+
+    /* Removes the rights tied to a king or rook starting square, which is called
+    with both squares of every move so that moving or capturing those pieces counts */
+    fn revoke(&mut self, square: Square) {
+        match (square.file(), square.rank()) {
+            (4, 0) => {
+                self.white_kingside = false;
+                self.white_queenside = false;
+            }
+            (7, 0) => self.white_kingside = false,
+            (0, 0) => self.white_queenside = false,
+            (4, 7) => {
+                self.black_kingside = false;
+                self.black_queenside = false;
+            }
+            (7, 7) => self.black_kingside = false,
+            (0, 7) => self.black_queenside = false,
+            _ => {}
+        }
+    }
+}
+
 // Defines a value() function for PieceTypes, which the AI uses
 impl PieceType {
     pub fn value(&self) -> i32 {
@@ -48,22 +442,152 @@ impl PieceType {
             _ => 0,
         }
     }
+
+    // The uppercase letter for the piece in FEN and SAN, e.g. 'N' for a knight
+    pub fn letter(&self) -> char {
+        match *self {
+            PieceType::Pawn => 'P',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+            PieceType::Corpse => 'X',
+        }
+    }
+
+    // Pair function to letter, which reads both uppercase and lowercase letters
+    pub fn from_letter(letter: char) -> Option<PieceType> {
+        match letter.to_ascii_uppercase() {
+            'P' => Some(PieceType::Pawn),
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'R' => Some(PieceType::Rook),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+}
+
+// Everything that decides if two positions are the same when looking for repetitions
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub board: [[Option<Piece>; 8]; 8],
+    pub color: Color,
+    pub castling_rights: CastlingRights,
+    pub en_passant: Option<Square>,
+    // The Zobrist key of the position, which is the same for positions that are the same
+    pub key: u64,
+}
+
+// The pieces a pawn can be promoted to, with the letters that choose them (e.g. "a7 a8q")
+const PROMOTION_PIECES: [(&str, PieceType); 4] = [
+    ("Q", PieceType::Queen),
+    ("R", PieceType::Rook),
+    ("B", PieceType::Bishop),
+    ("N", PieceType::Knight),
+];
+
+/* A draw offer waiting for an answer, with the length of the position history
+when it was made, so that it can be withdrawn after the offering color's next move */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawOffer {
+    pub color: Color,
+    pub offered_at: usize,
+}
+
+// The steps a knight can take, as (files, ranks)
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (1, 2),
+    (-1, 2),
+    (-1, -2),
+    (1, -2),
+    (2, -1),
+    (-2, 1),
+    (2, 1),
+    (-2, -1),
+];
+
+/* The steps a king can take, which are also the directions the other pieces slide in,
+where the middle four are the straight lines and the rest the diagonals */
+const KING_STEPS: [(i8, i8); 8] = [
+    (1, 1),
+    (-1, -1),
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (-1, 1),
+];
+
+type Board = [[Option<Piece>; 8]; 8];
+
+/* A move that has been made, with the piece that moved and everything about the
+game before it that the move can change, so that it can be taken back exactly */
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct MadeMove {
+    mv: Move,
+    piece: Piece,
+    captured: Option<Piece>,
+    changecolor: bool,
+    state: GameState,
+    color: Color,
+    en_passant: Option<Square>,
+    castling_rights: CastlingRights,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    draw_offer: Option<DrawOffer>,
+    key: u64,
 }
 
 pub struct Game {
     pub state: GameState,
-    pub board: [[Option<Piece>; 8]; 8],
+    /* Where the pieces are, which board() and set_board() read and replace as rows of
+    squares. It's private so that every bitboard and the key change together */
+    bitboards: Bitboards,
     pub color: Color,
+    // The square skipped by a pawn's double step on the last move, which can be captured en passant
+    pub en_passant: Option<Square>,
+    pub castling_rights: CastlingRights,
+    // Every position of the game so far, from the initial one up to the current one
+    pub history: Vec<Position>,
+    // Moves by either color since the last pawn move or capture
+    pub halfmove_clock: u32,
+    // Starts at 1 and goes up after every black move
+    pub fullmove_number: u32,
+    pub draw_offer: Option<DrawOffer>,
+    observers: Vec<Box<dyn GameObserver>>,
+    // The moves that have been made, which undo_move takes back starting from the last
+    move_stack: Vec<MadeMove>,
+    // The moves that have been taken back, with changecolor, which redo_move makes again
+    redo_stack: Vec<(Move, bool)>,
+    // The Zobrist key of the current position, which moves change and undos put back
+    key: u64,
 }
 
 impl Game {
     /// Initialises a new game with a board and the starting color white
     pub fn new() -> Game {
-        Game {
+        let mut game = Game {
             state: GameState::InProgress,
             color: Color::White,
-            board: Game::generate_board(),
-        }
+            bitboards: Bitboards::from_board(&Game::generate_board()),
+            en_passant: None,
+            castling_rights: CastlingRights::all(),
+            history: vec![],
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            draw_offer: None,
+            observers: vec![],
+            move_stack: vec![],
+            redo_stack: vec![],
+            key: 0,
+        };
+        game.refresh_zobrist_key();
+        game.history.push(game.get_position());
+        game
     }
 
     fn generate_board() -> [[Option<Piece>; 8]; 8] {
@@ -80,248 +604,351 @@ impl Game {
         ];
 
         // Fills the second and seventh row with pawns
-        for i in 0..8 {
-            currentboard[1][i] = Some(Piece {
-                piecetype: PieceType::Pawn,
-                color: Color::Black,
-            });
-            currentboard[6][i] = Some(Piece {
-                piecetype: PieceType::Pawn,
-                color: Color::White,
-            });
-        }
+        currentboard[1] = [Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: Color::Black,
+        }); 8];
+        currentboard[6] = [Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: Color::White,
+        }); 8];
 
         // Fills the first and last row with the right pieces
-        for i in 0..8 {
+        for (i, piecetype) in pieces.iter().enumerate() {
             currentboard[0][i] = Some(Piece {
-                piecetype: pieces[i],
+                piecetype: *piecetype,
                 color: Color::Black,
             });
             currentboard[7][i] = Some(Piece {
-                piecetype: pieces[i],
+                piecetype: *piecetype,
                 color: Color::White,
             });
         }
-
-        //currentboard = [[Some(Piece { piecetype: PieceType::Rook, color: Color::Black }), None, Some(Piece { piecetype: PieceType::Bishop, color: Color::Black }), None, Some(Piece { piecetype: PieceType::King, color: Color::Black }), Some(Piece { piecetype: PieceType::Bishop, color: Color::Black }), Some(Piece { piecetype: PieceType::Knight, color: Color::Black }), None], [Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Queen, color: Color::White }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None], [None, None, Some(Piece { piecetype: PieceType::Knight, color: Color::Black }), None, None, None, None, None], [Some(Piece { piecetype: PieceType::Queen, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black })], [None, Some(Piece { piecetype: PieceType::Rook, color: Color::Black }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None], [Some(Piece { piecetype: PieceType::Bishop, color: Color::White }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, None, None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White })], [None, Some(Piece { piecetype: PieceType::Rook, color: Color::White }), None, None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, Some(Piece { piecetype: PieceType::Rook, color: Color::White })], [None, Some(Piece { piecetype: PieceType::Knight, color: Color::White }), None, Some(Piece { piecetype: PieceType::King, color: Color::White }), None, Some(Piece { piecetype: PieceType::Bishop, color: Color::White }), Some(Piece { piecetype: PieceType::Knight, color: Color::White }), None]];
         currentboard
     }
 
-    /*Convert between the string (e.g. "A1") and vector (e.g. [0, 1])
-    representation of coordinates, because make_move uses strings as parameters*/
-    fn convert_string_to_vec(_position: String) -> Vec<i8> {
-        // Creates a hashmap with all letters associated to their position in the alphabet
-        let mut coordinate_hashmap: HashMap<String, i8> = HashMap::new();
-        let alphabet = vec!["A", "B", "C", "D", "E", "F", "G", "H"];
-        for i in 0..alphabet.iter().count() {
-            coordinate_hashmap.insert(alphabet[i].to_string(), i as i8);
-        }
-
-        // Get the first and second character, and then convert the second "letter" to an integer
-        let first_letter = _position.chars().nth(0).unwrap();
-        let second_letter = _position.chars().nth(1).unwrap().to_string();
-        let second_letter: i8 = second_letter.trim().parse().unwrap();
+    /* Returns the board as rows of squares, where row 0 is the eighth rank and each row
+    starts with the a-file. This used to be the board field, which can't be kept now that
+    the pieces are in bitboards, as a copy of them would get out of step */
+    pub fn board(&self) -> [[Option<Piece>; 8]; 8] {
+        self.bitboards.to_board()
+    }
 
-        // Gets the associated integer to the second character
-        let new_coordinate = vec![
-            coordinate_hashmap[&first_letter.to_string()],
-            8 - second_letter,
-        ];
+    // Puts the pieces as they stand on the board, in place of what was assigned to the board field
+    pub fn set_board(&mut self, board: [[Option<Piece>; 8]; 8]) {
+        self.bitboards = Bitboards::from_board(&board);
+        self.refresh_zobrist_key();
+    }
 
-        return new_coordinate;
+    // Returns the bitboards of the pieces, to read them without changing the game
+    pub fn bitboards(&self) -> &Bitboards {
+        &self.bitboards
     }
 
-    // Pair function to convert_string_to_vec
-    pub fn convert_vec_to_string(_position: &Vec<Vec<i8>>) -> Vec<String> {
-        let mut letter_coordinate_vec = vec![];
-        let letter_vec = ["A", "B", "C", "D", "E", "F", "G", "H"];
+    // Returns the piece standing on the square, if there is one
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.bitboards.piece_at(square)
+    }
 
-        /*Add the index in letter_vec corresponding to the vector's
-        first number to letter_coordinate_vec, as well as the second
-        number converted to a string*/
+    // Puts a piece on the square, or empties it with None
+    pub fn set_piece(&mut self, square: Square, piece: Option<Piece>) {
+        Game::change_keyed(self, |game| game.bitboards.set_piece(square, piece));
+    }
 
-        for i in 0.._position.iter().count() {
-            if _position[i][0] >= 0 && _position[i][1] >= 0 {
-            /*println!("{:?}", letter_vec[_position[i][0] as usize].to_string()
-                + &(8 - _position[i][1]).to_string());*/
-            letter_coordinate_vec.push(
-                letter_vec[_position[i][0] as usize].to_string()
-                    + &(8 - _position[i][1]).to_string(),
-            )
+    /// If the current game state is InProgress and the move is legal, move a piece
+    /// and return what the move did, or otherwise what kept it from being made.
+    pub fn make_move(&mut self, mv: Move, changecolor: bool) -> Result<MoveOutcome, MoveError> {
+        if Game::get_game_state(self).is_game_over() {
+            return Err(MoveError::GameOver);
         }
+        let own_piece = self.piece_at(mv.from).ok_or(MoveError::EmptySquare(mv.from))?;
+        if own_piece.color != self.color {
+            return Err(MoveError::WrongSide(self.color));
         }
-        return letter_coordinate_vec;
-    }
-
-    /// If the current game state is InProgress and the move is legal,
-    /// move a piece and return the resulting state of the game.
-    pub fn make_move(&mut self, _from: &String, _to: String, changecolor: bool) -> GameState {
-        if Game::get_game_state(self) == GameState::InProgress {
-            let piece_to_move = Game::convert_string_to_vec(_from.to_string());
-            let own_color = self.board[piece_to_move[1] as usize][piece_to_move[0] as usize]
-                .unwrap()
-                .color;
 
-            // Used to alert of invalid moves when playing in the terminal
-            let mut made_move = false;
-
-            if own_color == self.color {
-                let square_to_move_to = Game::convert_string_to_vec(_to);
+        /* Looks for the move among the possible moves of the piece, where a pawn
+        reaching the last rank has to be given the piece to promote to, and no
+        other move can have one */
+        let possible_moves = Game::get_possible_moves(self, mv.from, false);
+        let mut to_square = possible_moves
+            .into_iter()
+            .filter(|possible_move| possible_move.to == mv.to)
+            .peekable();
+        if to_square.peek().is_none() {
+            return Err(if Game::path_blocked(self, mv.from, mv.to) {
+                MoveError::BlockedPath
+            } else {
+                MoveError::IllegalMove
+            });
+        }
+        let found_move = to_square
+            .find(|possible_move| possible_move.promotion == mv.promotion)
+            .ok_or(MoveError::InvalidPromotion)?;
+        if Game::leaves_king_in_check(self, found_move) {
+            return Err(MoveError::LeavesKingInCheck);
+        }
 
-                let (_irrelevant, possible_moves) =
-                    Game::get_possible_moves(self, &piece_to_move, false);
+        let captured = if found_move.flags.en_passant {
+            Some(Piece {
+                piecetype: PieceType::Pawn,
+                color: Game::opposite_color_func(own_piece.color),
+            })
+        } else {
+            self.piece_at(found_move.to)
+        };
+        self.move_stack.push(MadeMove {
+            mv: found_move,
+            piece: own_piece,
+            captured,
+            changecolor,
+            state: self.state,
+            color: self.color,
+            en_passant: self.en_passant,
+            castling_rights: self.castling_rights,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            draw_offer: self.draw_offer,
+            key: self.key,
+        });
+        self.redo_stack.clear();
+        Game::move_piece(self, found_move);
+
+        if changecolor {
+            Game::emit(self, GameEvent::MoveMade(found_move));
+            if let Some(piece) = captured {
+                Game::emit(self, GameEvent::Capture { square: found_move.to, piece });
+            }
+            if let Some(piecetype) = found_move.promotion {
+                Game::emit(self, GameEvent::Promotion { square: found_move.to, piecetype });
+            }
 
-                /*Iterates through possible moves, and if it finds that the square to move to is
-                in possible moves, set previous position to None and the new position
-                to its previous position's data*/
-                for i in 0..possible_moves.iter().count() {
-                    if square_to_move_to[0] == possible_moves[i][0]
-                        && square_to_move_to[1] == possible_moves[i][1]
-                    {
-                        let ownpiecetype = self.board[piece_to_move[1] as usize]
-                            [piece_to_move[0] as usize]
-                            .unwrap()
-                            .piecetype;
-                        self.board[piece_to_move[1] as usize][piece_to_move[0] as usize] = None;
-                        self.board[square_to_move_to[1] as usize][square_to_move_to[0] as usize] =
-                            Some(Piece {
-                                piecetype: ownpiecetype,
-                                color: own_color,
-                            });
-                        made_move = true;
-                    }
+            // A draw offer lasts until its color moves after the other color has had a turn
+            if let Some(offer) = self.draw_offer {
+                if offer.color == self.color && self.history.len() > offer.offered_at {
+                    self.draw_offer = None;
                 }
+            }
 
-                if !made_move {
-                    println!("Invalid move!")
-                } else {
-                    // Changecolor (the last parameter) has to be false for checkmate to work
-                    if changecolor {
-                        println!("Changing color");
-                        if self.color == Color::White {
-                            self.color = Color::Black;
-                        } else {
-                            self.color = Color::White;
-                        }
-                    }
-                }
+            let opposite_color = Game::opposite_color_func(self.color);
+            Game::change_keyed(self, |game| game.color = opposite_color);
+            if self.color == Color::White {
+                // The counters can be loaded at their largest from a FEN, and stay there
+                self.fullmove_number = self.fullmove_number.saturating_add(1);
+            }
+            if found_move.flags.capture || own_piece.piecetype == PieceType::Pawn {
+                self.halfmove_clock = 0;
             } else {
-                // If the wrong player made the move
-                println!("It's {:?}'s turn, you know", self.color)
+                self.halfmove_clock = self.halfmove_clock.saturating_add(1);
             }
+            let position = Game::get_position(self);
+            self.history.push(position);
+            Game::update_game_state(self);
+        }
+
+        Ok(MoveOutcome {
+            mv: found_move,
+            captured,
+            promotion: found_move.promotion,
+            check: Game::king_attacked(self, Game::opposite_color_func(own_piece.color)),
+            state: Game::get_game_state(self),
+        })
+    }
+
+    /* Takes back the last move that was made, and puts the game back exactly as it
+    was before it. Returns the move, or None if no move has been made */
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let made = self.move_stack.pop()?;
+        let mv = made.mv;
+
+        self.set_piece(mv.from, Some(made.piece));
+        if mv.flags.en_passant {
+            self.set_piece(mv.to, None);
+            self.set_piece(Square::new(mv.to.file(), mv.from.rank()).unwrap(), made.captured);
+        } else {
+            self.set_piece(mv.to, made.captured);
+        }
+        if mv.flags.castling {
+            let (rook_from, rook_to) = if mv.to.file() == 6 { (7, 5) } else { (0, 3) };
+            let rook_from = Square::new(rook_from, mv.from.rank()).unwrap();
+            let rook_to = Square::new(rook_to, mv.from.rank()).unwrap();
+            self.set_piece(rook_from, self.piece_at(rook_to));
+            self.set_piece(rook_to, None);
+        }
+
+        self.state = made.state;
+        self.color = made.color;
+        self.en_passant = made.en_passant;
+        self.castling_rights = made.castling_rights;
+        self.halfmove_clock = made.halfmove_clock;
+        self.fullmove_number = made.fullmove_number;
+        self.draw_offer = made.draw_offer;
+        self.key = made.key;
+        if made.changecolor {
+            self.history.pop();
+        }
+        self.redo_stack.push((mv, made.changecolor));
+        Some(mv)
+    }
+
+    /* Makes the last move that was taken back again, and returns what it did. Returns
+    None if there is no such move, which is the case after any other move is made, or
+    if the move can't be made, e.g. after a resignation, and then it can still be redone */
+    pub fn redo_move(&mut self) -> Option<MoveOutcome> {
+        let (mv, changecolor) = self.redo_stack.pop()?;
+        let mut redo_stack = std::mem::take(&mut self.redo_stack);
+        let outcome = Game::make_move(self, mv, changecolor);
+        if outcome.is_err() {
+            redo_stack.push((mv, changecolor));
         }
+        self.redo_stack = redo_stack;
+        outcome.ok()
+    }
 
-        Game::set_promotion(self, PieceType::Queen);
-        Game::get_game_state(&self)
+    // Returns the moves made so far, starting with the first one
+    pub fn moves(&self) -> Vec<Move> {
+        self.move_stack.iter().map(|made| made.mv).collect()
     }
 
-    /// Set the piece type that a peasant becames following a promotion.
-    pub fn set_promotion(&mut self, _piece: PieceType) -> () {
-        /*Rotates through the first and last row, and if a
-        piece of the desired color is found, change it to _piece*/
-        for mut i in 0..16 {
-            let mut preffered_color = Color::White;
-            let row_to_check = if i > 7 {
-                i -= 8;
-                preffered_color = Color::Black;
-                7
+    /* Makes the move without checking that it's allowed, and updates the castling
+    and en passant rights that depend on the move */
+    fn move_piece(&mut self, mv: Move) {
+        Game::change_keyed(self, |game| {
+            game.bitboards.make_move(mv);
+            game.castling_rights.revoke(mv.from);
+            game.castling_rights.revoke(mv.to);
+
+            // The right to capture en passant only lasts for one move
+            game.en_passant = if mv.flags.double_pawn_push {
+                Square::new(mv.from.file(), (mv.from.rank() + mv.to.rank()) / 2)
             } else {
-                0
+                None
             };
-            if self.board[row_to_check][i] != None {
-                let own_color = self.board[row_to_check][i].unwrap().color;
-                if self.board[row_to_check][i].unwrap().piecetype == PieceType::Pawn
-                    && own_color == preffered_color
-                {
-                    let own_color = self.board[row_to_check][i].unwrap().color;
-                    self.board[row_to_check][i as usize] = Some(Piece {
-                        piecetype: _piece,
-                        color: own_color,
-                    });
-                }
-            }
-        }
+        });
     }
 
-    // Plays the game in the terminal with string inputs
+    /* Plays the game in the terminal with string inputs, where what happens in the
+    game is printed by a TerminalPrinter for as long as it's played */
     pub fn play_the_game(&mut self) {
         let stdin = io::stdin();
+        Game::subscribe(self, Box::new(TerminalPrinter));
         Game::print(self);
         println!("White, enter your first move: ");
 
-        // Creates a vector with all valid moves, to check against input later
-        let mut all_valid = vec![];
-        let alphabet = vec!["A", "B", "C", "D", "E", "F", "G", "H"];
-        for i in 1..9 {
-            for j in 1..9 {
-                all_valid.push(format!("{}{}", alphabet[i - 1].to_string(), j.to_string()))
-            }
-        }
-
-        /* Gets the first line (only relevant one), and if its length is
-        right the string is converted to uppercase and a move is made */
+        /* Gets the first line (only relevant one), and if it's one of the commands
+        that is done, and otherwise it's read as a move */
         for line in stdin.lock().lines() {
             let unwrapped = line.unwrap();
-            let unwrapped: &str = &*unwrapped;
-            if &unwrapped.len() > &(4 as usize) {
-                let start_position = &unwrapped[0..2].to_uppercase();
-                let finalposition = &unwrapped[3..5].to_uppercase();
-                if all_valid.contains(&start_position) && all_valid.contains(&finalposition) {
-                    Game::make_move(
-                        self,
-                        &start_position.to_string(),
-                        finalposition.to_string(),
-                        true,
-                    );
+            let unwrapped: &str = &unwrapped;
+            if unwrapped.trim() == "draw" {
+                if Game::claim_draw(self) {
+                    break;
+                }
+                println!("You can't claim a draw now! Enter new:")
+            } else if unwrapped.trim() == "resign" {
+                println!("{:?} resigns!", self.color);
+                Game::resign(self, self.color);
+                break;
+            } else if unwrapped.trim() == "offer" {
+                Game::offer_draw(self, self.color);
+                println!("{:?} offers a draw. Enter your move:", self.color)
+            } else if unwrapped.trim() == "accept" {
+                if Game::accept_draw(self, self.color) {
+                    break;
+                }
+                println!("There's no draw offer to accept! Enter new:")
+            } else if unwrapped.trim() == "undo" || unwrapped.trim() == "redo" {
+                let changed = if unwrapped.trim() == "undo" {
+                    Game::undo_move(self).is_some()
+                } else {
+                    Game::redo_move(self).is_some()
+                };
+                if changed {
                     Game::print(self);
-                    println!("{:?}, enter your move: ", self.color);
+                    println!("{:?}, enter your move: ", self.color)
                 } else {
-                    println!("Invalid move! Enter new:")
+                    println!("There's no move to {}! Enter new:", unwrapped.trim())
+                }
+            } else if unwrapped.trim() == "pgn" {
+                println!("{}", Game::to_pgn(self, &PgnTags::default(), &[]));
+                println!("{:?}, enter your move: ", self.color)
+            } else if unwrapped.trim() == "decline" {
+                if Game::decline_draw(self, self.color) {
+                    println!("{:?} declines the draw. Enter your move:", self.color)
+                } else {
+                    println!("There's no draw offer to decline! Enter new:")
                 }
             } else {
-                println!("Invalid move! Enter new:")
+                // A move is read as two squares if it can be, and otherwise as SAN
+                let mv = match unwrapped.parse::<Move>() {
+                    Ok(mv) => mv,
+                    Err(_) => match Game::parse_san(self, unwrapped.trim()) {
+                        Ok(mv) => mv,
+                        Err(error) => {
+                            println!("{}! Enter new:", error);
+                            continue;
+                        }
+                    },
+                };
+                let color = self.color;
+                let san = Game::to_san(self, mv);
+                let outcome = match Game::make_move(self, mv, true) {
+                    Ok(outcome) => outcome,
+                    Err(error) => {
+                        println!("{}! Enter new:", error);
+                        continue;
+                    }
+                };
+                if let Ok(san) = san {
+                    println!("{:?} plays {}", color, san);
+                }
+                Game::print(self);
+                if outcome.state.is_game_over() {
+                    break;
+                }
+                println!("{:?}, enter your move: ", self.color);
             }
         }
+        self.observers.pop();
     }
 
     // Prints the board in unicode
     pub fn print(&self) {
         println!("#-A--B--C--D--E--F--G--H-#");
         let mut lineiter = 9;
-        for line in self.board {
+        for line in self.board() {
             lineiter -= 1;
             print!("{:?}", lineiter);
             for piece in line {
-                if piece != None {
-                    if piece.unwrap().color == Color::Black {
-                        if piece.unwrap().piecetype == PieceType::Pawn {
-                            print!(" {} ", "♙");
-                        } else if piece.unwrap().piecetype == PieceType::Rook {
-                            print!(" {} ", "♖");
-                        } else if piece.unwrap().piecetype == PieceType::Knight {
-                            print!(" {} ", "♘");
-                        } else if piece.unwrap().piecetype == PieceType::Queen {
-                            print!(" {} ", "♕");
-                        } else if piece.unwrap().piecetype == PieceType::King {
-                            print!(" {} ", "♔");
-                        } else if piece.unwrap().piecetype == PieceType::Bishop {
-                            print!(" {} ", "♗");
+                if let Some(piece) = piece {
+                    if piece.color == Color::Black {
+                        if piece.piecetype == PieceType::Pawn {
+                            print!(" ♙ ");
+                        } else if piece.piecetype == PieceType::Rook {
+                            print!(" ♖ ");
+                        } else if piece.piecetype == PieceType::Knight {
+                            print!(" ♘ ");
+                        } else if piece.piecetype == PieceType::Queen {
+                            print!(" ♕ ");
+                        } else if piece.piecetype == PieceType::King {
+                            print!(" ♔ ");
+                        } else if piece.piecetype == PieceType::Bishop {
+                            print!(" ♗ ");
                         }
                     } else {
-                        if piece.unwrap().piecetype == PieceType::Pawn {
-                            print!(" {} ", "♟︎");
-                        } else if piece.unwrap().piecetype == PieceType::Rook {
-                            print!(" {} ", "♜");
-                        } else if piece.unwrap().piecetype == PieceType::Knight {
-                            print!(" {} ", "♞");
-                        } else if piece.unwrap().piecetype == PieceType::Queen {
-                            print!(" {} ", "♛");
-                        } else if piece.unwrap().piecetype == PieceType::King {
-                            print!(" {} ", "♚");
-                        } else if piece.unwrap().piecetype == PieceType::Bishop {
-                            print!(" {} ", "♝");
-                        } else if piece.unwrap().piecetype == PieceType::Corpse {
-                            print!(" {} ", "x"); //☠️
+                        if piece.piecetype == PieceType::Pawn {
+                            print!(" ♟︎ ");
+                        } else if piece.piecetype == PieceType::Rook {
+                            print!(" ♜ ");
+                        } else if piece.piecetype == PieceType::Knight {
+                            print!(" ♞ ");
+                        } else if piece.piecetype == PieceType::Queen {
+                            print!(" ♛ ");
+                        } else if piece.piecetype == PieceType::King {
+                            print!(" ♚ ");
+                        } else if piece.piecetype == PieceType::Bishop {
+                            print!(" ♝ ");
+                        } else if piece.piecetype == PieceType::Corpse {
+                            print!(" x "); //☠️
                         }
                     }
                 } else {
@@ -346,489 +973,473 @@ impl Game {
         }
     }
 
-    /* Gets all possible moves from a certain color by repeatedly calling
-    get_possible_moves for all the pieces it finds by iterating through the board */
-    pub fn get_all_possible_moves(
-        &mut self,
-        opposite_color: &Color,
-    ) -> (Vec<Vec<i8>>, HashMap<Vec<i8>, Vec<i8>>) {
+    /* Gets all legal moves of a color by calling get_possible_moves for all the
+    pieces it finds on the board, with one move for every piece a pawn can be
+    promoted to */
+    pub fn get_all_possible_moves(&self, color: Color) -> Vec<Move> {
         let mut all_possible_moves = vec![];
-        let mut move_from_to_hashmap: HashMap<Vec<i8>, Vec<i8>> = HashMap::new();
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.board[j as usize][i as usize] != None {
-                    if self.board[j as usize][i as usize].unwrap().color == *opposite_color {
-                        let (_irrelevant, possible_moves) =
-                            Game::get_possible_moves(self, &vec![i, j], false);
-                        for n in possible_moves {
-                            move_from_to_hashmap.insert(n.clone(), vec![i, j]);
-                            all_possible_moves.push(n);
-                        }
-                    }
-                }
-            }
+        for square in bitboard::squares(self.bitboards.color(color)) {
+            all_possible_moves.extend(Game::get_possible_moves(self, square, true));
         }
-        return (all_possible_moves, move_from_to_hashmap);
+        all_possible_moves
     }
 
-    // Returns the king's position on the board
-    fn get_king_position(&mut self) -> Vec<i8> {
-        let mut king_position = vec![];
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.board[j as usize][i as usize] != None {
-                    if self.board[j as usize][i as usize].unwrap().color == self.color
-                        && self.board[j as usize][i as usize].unwrap().piecetype == PieceType::King
-                    {
-                        king_position = vec![i, j]
-                    }
-                }
-            }
-        }
-        return king_position;
+    /* Returns if any piece of the color attacks the square, whatever stands on it, by
+    looking outwards from the square instead of generating moves. Pawns attack the two
+    squares diagonally in front of them, whether they can move there or not */
+    pub fn is_square_attacked(&self, square: Square, by: Color) -> bool {
+        self.bitboards.is_attacked(square, by)
+    }
+
+    // Returns the squares of the pieces of the color that attack the square, from a1 upwards
+    pub fn attackers_of(&self, square: Square, color: Color) -> Vec<Square> {
+        bitboard::squares(self.bitboards.attackers(square, color)).collect()
     }
 
-    // Returns if the king is in check or not
+    /* Returns the squares of the pieces giving check to the current color, which is
+    empty when it isn't in check, or None if the current color has no king to check */
+    pub fn checkers(&self) -> Option<Vec<Square>> {
+        let king_position = self.bitboards.king_square(self.color)?;
+        Some(Game::attackers_of(self, king_position, Game::opposite_color_func(self.color)))
+    }
+
+    /* Returns the squares of the pieces of the color that are pinned to their king, which
+    can only move along the line between the king and the piece pinning them, or None if
+    the color has no king */
+    pub fn pinned_pieces(&self, color: Color) -> Option<Vec<Square>> {
+        self.bitboards.king_square(color)?;
+        Some(bitboard::squares(self.bitboards.pinned(color)).collect())
+    }
+
+    // Returns if the king is in check or not, and updates the state unless the game is over
     pub fn check_check(&mut self) -> bool {
-        let own_color = self.color;
+        let check = Game::king_attacked(self, self.color);
+        if !self.state.is_game_over() {
+            self.state = if check { GameState::Check } else { GameState::InProgress };
+        }
+        check
+    }
+
+    /* Returns if the king of the given color is attacked, without touching the game state.
+    A color without a king can't be in check, so a position set up without one can still
+    be played, but it's never checkmate */
+    fn king_attacked(&self, color: Color) -> bool {
+        let opposite_color = Game::opposite_color_func(color);
+        match self.bitboards.king_square(color) {
+            Some(king_position) => Game::is_square_attacked(self, king_position, opposite_color),
+            None => false,
+        }
+    }
+
+    /* Returns if making the move would leave the king of the moving piece attacked,
+    by trying it on a copy of the bitboards */
+    fn leaves_king_in_check(&self, mv: Move) -> bool {
+        let own_color = match self.piece_at(mv.from) {
+            Some(piece) => piece.color,
+            None => return false,
+        };
+        let mut bitboards = self.bitboards;
+        bitboards.make_move(mv);
         let opposite_color = Game::opposite_color_func(own_color);
-        let king_position = Game::get_king_position(self);
+        match bitboards.king_square(own_color) {
+            Some(king_position) => bitboards.is_attacked(king_position, opposite_color),
+            None => false,
+        }
+    }
 
-        let (all_possible_moves, _irrelevantmap) = self.get_all_possible_moves(&opposite_color);
-        if all_possible_moves.contains(&king_position) {
-            println!("CHESS! CHESS! CHESS!");
-            self.state = GameState::Check;
-            return true;
-        } else {
-            println!("NO CHESS! NO CHESS! NO CHESS!");
+    /* Returns if there is a piece between the two squares that stops the piece on
+    from from getting to to, which is only the case if the piece could have slid
+    there along a line on an empty board */
+    fn path_blocked(&self, from: Square, to: Square) -> bool {
+        let files = to.file() as i8 - from.file() as i8;
+        let ranks = to.rank() as i8 - from.rank() as i8;
+        let straight = files == 0 || ranks == 0;
+        let diagonal = files.abs() == ranks.abs();
+        let slides_there = match self.piece_at(from).map(|piece| piece.piecetype) {
+            Some(PieceType::Rook) => straight,
+            Some(PieceType::Bishop) => diagonal,
+            Some(PieceType::Queen) => straight || diagonal,
+            // A pawn's double step and castling are the only moves where they pass a square
+            Some(PieceType::Pawn) => files == 0 && ranks.abs() == 2,
+            Some(PieceType::King) => ranks == 0 && files.abs() == 2,
+            _ => false,
+        };
+        if !slides_there {
             return false;
         }
-    }
 
-    // Returns if it's checkmate or not.
-    pub fn checkmate(&mut self) -> bool {
-        let own_color = self.color;
-        let (myall_possible_moves, useful_hashmap) = self.get_all_possible_moves(&own_color);
-        self.print();
-        let mut checkmate = true;
-
-        /* Iterates through all your possible moves, makes the move,
-        checks if it's still check, sets checkmate to false if it's
-        not check for any move, and reverts to the original boardstate.*/
-        for i in 0..myall_possible_moves.iter().count() {
-            let saved_boardstate = self.board;
-            self.make_move(
-                &Game::convert_vec_to_string(&vec![useful_hashmap
-                    [&myall_possible_moves[i].clone()]
-                    .clone()])[0]
-                    .clone(),
-                Game::convert_vec_to_string(&vec![myall_possible_moves[i].clone()])[0].clone(),
-                false,
-            );
-            let check = Game::check_check(self);
-            self.board = saved_boardstate;
-            if !check {
-                checkmate = false;
+        let step = (files.signum(), ranks.signum());
+        let mut current = from.offset(step.0, step.1);
+        while let Some(between) = current {
+            if between == to {
+                break;
             }
-        }
-        println!("checkmate: {:?} for {:?}", checkmate, self.color);
-        if checkmate {
-            self.state = GameState::GameOver
-        }
-        //println!("testing board: {:?}", self.board);
-        return checkmate;
-    }
-
-    /* If a piece is standing on the given tile, return all possible
-    new positions of that piece, taking the rules for check into account*/
-    pub fn get_possible_moves(
-        &mut self,
-        _position: &Vec<i8>,
-        should_check: bool,
-    ) -> (Vec<String>, Vec<Vec<i8>>) {
-        // Reverts corpses (possible moves, visualized as X's, during debugging) to None
-        for i in 0..8 {
-            for j in 0..8 {
-                if self.board[j as usize][i as usize]
-                    == Some(Piece {
-                        piecetype: PieceType::Corpse,
-                        color: Color::White,
-                    })
-                {
-                    self.board[j as usize][i as usize] = None
-                }
+            if self.piece_at(between).is_some() {
+                return true;
             }
+            current = between.offset(step.0, step.1);
         }
+        false
+    }
 
-        let my_piece = self.board[_position[1] as usize][_position[0] as usize];
-        if my_piece == None {
-            //println!("Do nothing");
-            return (vec!["".to_string()], vec![vec![0, 0]]);
-        } else {
-            let own_color = my_piece.unwrap().color;
-            let current_piecetype = my_piece.unwrap().piecetype;
-            let opposite_color = Game::opposite_color_func(own_color);
-
-            // Adds two i8's together, because usize can't be negative
-            fn convert_usize(possiblenegative: i8, othertoconvert: i8) -> usize {
-                let sum = possiblenegative + othertoconvert;
-                if sum < 0 || sum > 7 {
-                    return 7 as usize;
-                } else {
-                    return sum as usize;
-                }
+    /* Sets the state after a move. The game ends if the current color can't move, which
+    is checkmate if it's in check and stalemate otherwise, if neither side has enough
+    pieces left to checkmate, if the same position has appeared five times, or if 75
+    moves have been made by each side without a pawn move or capture */
+    fn update_game_state(&mut self) {
+        let check = Game::king_attacked(self, self.color);
+        if check {
+            Game::emit(self, GameEvent::Check(self.color));
+        }
+        let draw_reason = if Game::get_all_possible_moves(self, self.color).is_empty() {
+            if check {
+                let winner = Game::opposite_color_func(self.color);
+                Game::end_game(self, GameResult::Checkmate { winner });
+                return;
             }
+            Some(DrawReason::Stalemate)
+        } else if Game::dead_position(self) {
+            Some(DrawReason::DeadPosition)
+        } else if Game::repetitions(self) >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(DrawReason::SeventyFiveMoveRule)
+        } else {
+            None
+        };
 
-            /*Adds the positions in the current_vector to possible_moves if
-            they are in the limits of the board*/
-            fn add_function(
-                current_vector: Vec<Vec<i8>>,
-                mut possible_moves: Vec<Vec<i8>>,
-                position: &Vec<i8>,
-            ) -> Vec<Vec<i8>> {
-                for i in 0..current_vector.iter().count() {
-                    if position[0] + current_vector[i][0] < 8
-                        && position[0] + current_vector[i][0] >= 0
-                        && position[1] + current_vector[i][1] >= 0
-                        && position[1] + current_vector[i][1] < 8
-                    {
-                        possible_moves.push(vec![
-                            position[0] + current_vector[i][0],
-                            position[1] + current_vector[i][1],
-                        ]);
-                    }
-                }
-                return possible_moves;
-            }
+        match draw_reason {
+            Some(reason) => Game::end_game(self, GameResult::Draw(reason)),
+            None if check => self.state = GameState::Check,
+            None => self.state = GameState::InProgress,
+        }
+    }
 
-            // Adds diagonal lines for the bishop and queen pieces
-            fn bishop_function(
-                bishop_vector: &mut Vec<Vec<i8>>,
-                board: &[[Option<Piece>; 8]; 8],
-                color: &Color,
-                position: &Vec<i8>,
-            ) -> Vec<Vec<i8>> {
-                let mut continue_loop = true;
-                for fakei in 0..32 {
-                    // Goes from the input piece out in all directions, unless halted by continue_loop
-                    if fakei % 8 == 0 {
-                        continue_loop = true;
-                    }
+    // Ends the game with the result, and lets the observers know unless it was already over
+    fn end_game(&mut self, result: GameResult) {
+        if !self.state.is_game_over() {
+            self.state = GameState::GameOver(result);
+            Game::emit(self, GameEvent::GameOver(result));
+        }
+    }
 
-                    let i = if fakei < 8 {
-                        fakei
-                    } else if fakei < 16 {
-                        -(fakei - 8)
-                    } else if fakei < 24 {
-                        -(fakei - 16)
-                    } else {
-                        fakei - 24
-                    };
-
-                    let j = if fakei < 8 {
-                        fakei
-                    } else if fakei < 16 {
-                        fakei - 8
-                    } else if fakei < 24 {
-                        -(fakei - 16)
-                    } else {
-                        -(fakei - 24)
-                    };
+    // Adds an observer that gets every event of the game from now on
+    pub fn subscribe(&mut self, observer: Box<dyn GameObserver>) {
+        self.observers.push(observer);
+    }
 
-                    if board[convert_usize(position[1], i)][convert_usize(position[0], j)] != None
-                        && continue_loop
-                    {
-                        if board[convert_usize(position[1], i)][convert_usize(position[0], j)]
-                            .unwrap()
-                            .color
-                            != *color
-                        {
-                            // Adds a potential capture
-                            bishop_vector.push(vec![position[0] + j, position[1] + i]);
-                            // Stops if encounters a piece (and the piece isn't itself)
-                            if i != 0 {
-                                continue_loop = false;
-                            }
-                        } else {
-                            // Stops loop if it encounters a piece of the same color
-                            if i != 0 {
-                                continue_loop = false;
-                            }
-                        }
-                    } else if continue_loop {
-                        // Adds empty square to
-                        bishop_vector.push(vec![position[0] + j, position[1] + i]);
-                    }
-                }
-                //println!("bishop_vector: {:?}", bishop_vector.to_vec());
-                //println!("In bishop function");
-                return bishop_vector.to_vec();
-            }
+    // Sends the event to every observer
+    fn emit(&mut self, event: GameEvent) {
+        for observer in self.observers.iter_mut() {
+            observer.on_event(&event);
+        }
+    }
 
-            // Adds vertical and horizontal lines to rook and queen's possible moves
-            fn rook_function(
-                possible_moves: &mut Vec<Vec<i8>>,
-                position: &Vec<i8>,
-                board: &[[Option<Piece>; 8]; 8],
-                color: &Color,
-            ) -> Vec<Vec<i8>> {
-                let mut continue_xloop = true;
-                let mut continue_yloop = true;
-
-                // Specifies which direction to go in (starts from the rook's/queen's square)
-                let mut go_up_iter = 0;
-                let mut go_up = true;
-
-                for mut i in -7..8 {
-                    if i == 0 {
-                        continue_xloop = true;
-                        continue_yloop = true;
-                    }
-                    if go_up {
-                        go_up_iter -= 1;
-                        i = go_up_iter;
-                    }
+    /* Returns the current position as it counts for repetitions, where the en passant
+    square only matters if a pawn of the current color stands ready to capture on it */
+    pub fn get_position(&self) -> Position {
+        Position {
+            board: self.board(),
+            color: self.color,
+            castling_rights: self.castling_rights,
+            en_passant: Game::capturable_en_passant(self),
+            key: Game::zobrist_key(self),
+        }
+    }
 
-                    // I think a bug appears if there's a block on both x and y
-                    if (go_up_iter > 8 || go_up_iter < -8) && go_up {
-                        go_up_iter = 0;
-                        go_up = false
-                    }
-                    if !go_up {
-                        go_up_iter += 1;
-                        i = go_up_iter;
-                    }
+    // Returns the en passant square if a pawn of the current color stands ready to capture on it
+    fn capturable_en_passant(&self) -> Option<Square> {
+        let own_pawn = Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: self.color,
+        });
+        self.en_passant.filter(|target| {
+            let ranks = if self.color == Color::White { -1 } else { 1 };
+            [target.offset(-1, ranks), target.offset(1, ranks)]
+                .iter()
+                .any(|square| square.and_then(|square| self.piece_at(square)) == own_pawn)
+        })
+    }
 
-                    // Add horizontal lines, and stop if it encounters a piece
-                    if position[0] + i < 8 && position[0] + i >= 0 && continue_xloop {
-                        if board[position[1] as usize][convert_usize(position[0], i)] != None {
-                            if board[position[1] as usize][convert_usize(position[0], i)]
-                                .unwrap()
-                                .color
-                                != *color
-                            {
-                                possible_moves.push(vec![position[0] + i, position[1]]);
-                                if i != 0 {
-                                    continue_xloop = false;
-                                }
-                            } else {
-                                if i != 0 {
-                                    continue_xloop = false;
-                                }
-                            }
-                        } else {
-                            possible_moves.push(vec![position[0] + i, position[1]]);
-                        }
-                    }
+    // Returns how many times the current position has appeared, counting the current one
+    pub fn repetitions(&self) -> usize {
+        // Positions with different keys can't be the same, which saves comparing the boards
+        let position = Game::get_position(self);
+        self.history
+            .iter()
+            .filter(|previous| previous.key == position.key && **previous == position)
+            .count()
+    }
 
-                    // Add vertical lines, and stop if it encounters a piece
-                    if position[1] + i < 8 && position[1] + i >= 0 && continue_yloop {
-                        if board[convert_usize(position[1], i)][position[0] as usize] != None {
-                            if board[convert_usize(position[1], i)][position[0] as usize]
-                                .unwrap()
-                                .color
-                                != *color
-                            {
-                                possible_moves.push(vec![position[0], position[1] + i]);
-                                if i != 0 {
-                                    continue_yloop = false;
-                                }
-                            } else {
-                                if i != 0 {
-                                    continue_yloop = false;
-                                }
-                            }
-                        } else {
-                            possible_moves.push(vec![position[0], position[1] + i]);
-                        }
-                    }
-                }
-                return possible_moves.to_vec();
+    /* Ends the game as a draw if the current position has appeared at least three times,
+    or if 50 moves have been made by each side without a pawn move or capture, which
+    a player has to claim, and returns if the claim was accepted */
+    pub fn claim_draw(&mut self) -> bool {
+        if Game::get_game_state(self).is_game_over() {
+            return false;
+        }
+        let reason = if Game::repetitions(self) >= 3 {
+            DrawReason::ThreefoldRepetition
+        } else if self.halfmove_clock >= 100 {
+            DrawReason::FiftyMoveRule
+        } else {
+            return false;
+        };
+        Game::end_game(self, GameResult::Draw(reason));
+        true
+    }
+
+    // Ends the game when the given color has run out of time
+    pub fn timeout(&mut self, color: Color) {
+        if !Game::get_game_state(self).is_game_over() {
+            let winner = Game::opposite_color_func(color);
+            Game::end_game(self, GameResult::Timeout { winner });
+        }
+    }
+
+    // Ends the game as a win for the other color
+    pub fn resign(&mut self, color: Color) {
+        if !Game::get_game_state(self).is_game_over() {
+            let winner = Game::opposite_color_func(color);
+            Game::end_game(self, GameResult::Resignation { winner });
+        }
+    }
+
+    /* Offers the other color a draw, which it can accept or decline until the offering
+    color has made its next move, and returns if the offer could be made */
+    pub fn offer_draw(&mut self, color: Color) -> bool {
+        if Game::get_game_state(self).is_game_over() {
+            return false;
+        }
+        self.draw_offer = Some(DrawOffer {
+            color,
+            offered_at: self.history.len(),
+        });
+        true
+    }
+
+    // Ends the game as a draw if the other color has offered one, and returns if it did
+    pub fn accept_draw(&mut self, color: Color) -> bool {
+        match self.draw_offer {
+            Some(offer) if offer.color != color && !self.state.is_game_over() => {
+                self.draw_offer = None;
+                Game::end_game(self, GameResult::Draw(DrawReason::Agreement));
+                true
             }
+            _ => false,
+        }
+    }
 
-            let mut new_position = if current_piecetype == PieceType::Pawn {
-                let to_add_one = if own_color == Color::Black { 1 } else { -1 };
-                let to_add_two = if own_color == Color::Black { 2 } else { -2 };
-                let start_position = if own_color == Color::Black { 1 } else { 6 };
-                let promotion_position = if own_color == Color::Black { 7 } else { 0 };
-                let mut possible_moves = vec![];
-
-                // Adds one step forward to possible_moves if the square is empty
-                if self.board[convert_usize(_position[1], to_add_one)][_position[0] as usize]
-                    == None
-                {
-                    possible_moves.push(vec![_position[0], _position[1] + to_add_one]);
-                }
+    // Turns down a draw offered by the other color, and returns if there was one
+    pub fn decline_draw(&mut self, color: Color) -> bool {
+        match self.draw_offer {
+            Some(offer) if offer.color != color => {
+                self.draw_offer = None;
+                true
+            }
+            _ => false,
+        }
+    }
 
-                // Adds two steps forward to possible_moves if the pawn is in its initial position
-                if _position[1] == start_position
-                    && self.board[convert_usize(_position[1], to_add_two)][_position[0] as usize]
-                        == None
-                    && self.board[convert_usize(_position[1], to_add_one)][_position[0] as usize]
-                        == None
-                {
-                    possible_moves.push(vec![_position[0], _position[1] + to_add_two]);
+    /* Returns if neither side can checkmate whatever moves are made, which is the case
+    when only kings are left, plus at most one knight or bishop, or plus any number of
+    bishops that all stand on the same square color */
+    pub fn dead_position(&self) -> bool {
+        let mut minor_pieces = 0;
+        let mut bishop_square_colors = vec![];
+        for square in bitboard::squares(self.bitboards.occupied()) {
+            match self.piece_at(square).unwrap().piecetype {
+                PieceType::King | PieceType::Corpse => {}
+                PieceType::Knight => minor_pieces += 1,
+                PieceType::Bishop => {
+                    minor_pieces += 1;
+                    bishop_square_colors.push((square.file() + square.rank()) % 2);
                 }
+                _ => return false,
+            }
+        }
+
+        minor_pieces <= 1
+            || (bishop_square_colors.len() == minor_pieces
+                && bishop_square_colors.iter().all(|color| *color == bishop_square_colors[0]))
+    }
+
+    // Returns if it's checkmate or not.
+    pub fn checkmate(&mut self) -> bool {
+        let no_moves = Game::get_all_possible_moves(self, self.color).is_empty();
+        let checkmate = Game::king_attacked(self, self.color) && no_moves;
+        if checkmate {
+            let winner = Game::opposite_color_func(self.color);
+            Game::end_game(self, GameResult::Checkmate { winner });
+        }
+        checkmate
+    }
 
-                // Adds the diagonal capture move if a piece of the opposite color is there
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if self.board[i][j] != None {
-                            if self.board[i][j].unwrap().color == opposite_color {
-                                if i as i8 == _position[1] + to_add_one
-                                    && (j as i8 == _position[0] + 1 || j as i8 == _position[0] - 1)
-                                {
-                                    possible_moves.push(vec![j as i8, i as i8]);
-                                }
+    // Returns if it's stalemate, meaning the current color isn't in check but has no legal moves
+    pub fn stalemate(&mut self) -> bool {
+        let no_moves = Game::get_all_possible_moves(self, self.color).is_empty();
+        let stalemate = !Game::king_attacked(self, self.color) && no_moves;
+        if stalemate {
+            Game::end_game(self, GameResult::Draw(DrawReason::Stalemate));
+        }
+        stalemate
+    }
+
+    /* If a piece is standing on the given square, return all possible
+    moves of that piece, taking the rules for check into account*/
+    pub fn get_possible_moves(&self, square: Square, should_check: bool) -> Vec<Move> {
+        let my_piece = match self.piece_at(square) {
+            Some(piece) => piece,
+            None => return vec![],
+        };
+
+        let own_color = my_piece.color;
+        let current_piecetype = my_piece.piecetype;
+        let opposite_color = Game::opposite_color_func(own_color);
+
+        // A piece can move to the squares it attacks, unless one of its own pieces is there
+        let own_pieces = self.bitboards.color(own_color);
+        let attacked_squares = self.bitboards.attacks(square) & !own_pieces;
+
+        let mut possible_squares = vec![];
+        match current_piecetype {
+            PieceType::Pawn => {
+                let forward = if own_color == Color::White { 1 } else { -1 };
+                let start_rank = if own_color == Color::White { 1 } else { 6 };
+
+                /* Adds one step forward if the square is empty, and two steps forward if
+                the pawn is in its initial position and both squares are empty */
+                if let Some(one_step) = square.offset(0, forward) {
+                    if self.piece_at(one_step).is_none() {
+                        possible_squares.push(one_step);
+                        if let Some(two_steps) = square.offset(0, 2 * forward) {
+                            if square.rank() == start_rank && self.piece_at(two_steps).is_none() {
+                                possible_squares.push(two_steps);
                             }
-                            // Start of en passant below
-                            /*if self.board[i][j].unwrap().color == opposite_color {
-                                if j as i8 == 6 - start_position && j as i8 == _position[1] + to_add_one
-                                    && i as i8 == _position[1] + to_add_one
-                                {
-                                    //println!("Index {} {}", j, i);
-                                    //println!("{:?}", self.board[i][j].unwrap());
-                                    possible_moves.push(vec![j as i8, i as i8]);
-                                }
-                            }*/
                         }
                     }
                 }
 
-                possible_moves
-            } else if current_piecetype == PieceType::Rook {
-                let mut possible_moves = vec![];
-                possible_moves =
-                    rook_function(&mut possible_moves, &_position, &self.board, &own_color);
-                possible_moves
-            } else if current_piecetype == PieceType::Knight {
-                let mut possible_moves = vec![];
-
-                // All the different moves a knight can make (relative to current position)
-                let knight_vector = vec![
-                    vec![1, 2],
-                    vec![-1, 2],
-                    vec![-1, -2],
-                    vec![1, -2],
-                    vec![2, -1],
-                    vec![-2, 1],
-                    vec![2, 1],
-                    vec![-2, -1],
-                ];
-
-                possible_moves = add_function(knight_vector, possible_moves, &_position);
-                possible_moves
-            } else if current_piecetype == PieceType::King {
-                let mut possible_moves = vec![];
-
-                // All the different moves a king can make (relative to current position)
-                let kingvector = vec![
-                    vec![1, 1],
-                    vec![-1, -1],
-                    vec![-1, 0],
-                    vec![1, 0],
-                    vec![0, -1],
-                    vec![0, 1],
-                    vec![1, -1],
-                    vec![-1, 1],
-                ];
-
-                possible_moves = add_function(kingvector, possible_moves, &_position);
-                possible_moves
-            } else if current_piecetype == PieceType::Bishop {
-                let mut bishop_vector = vec![];
-                let possible_moves = bishop_function(&mut bishop_vector, &self.board, &own_color, &_position);
-                //println!("{:?}", possible_moves);
-                possible_moves
-            } else if current_piecetype == PieceType::Queen {
-                let mut queen_vector = vec![];
-                let mut possible_moves =
-                    bishop_function(&mut queen_vector, &self.board, &own_color, &_position);
-                rook_function(&mut possible_moves, &_position, &self.board, &own_color);
-
-                possible_moves
-            } else {
-                vec![vec![0, 3]]
-            };
-
-            // Sort out the piece's current position from new_position, and negative 
-            new_position.retain(|x| !(x[0] == _position[0] && x[1] == _position[1]) && (x[0] >= 0 && x[1] >= 0) && (x[0] < 8 && x[1] < 8));
-
-            // Sort out duplicates
-            new_position.dedup();
-
-            //println!("{:?}", new_position);
-
-            // Removes moves which contain a piece of the same color (only necessary for knight and pawn)
-            let mut new_new_position: Vec<Vec<i8>> = vec![];
-            if current_piecetype == PieceType::Pawn || current_piecetype == PieceType::Knight {
-                for i in 0..8 {
-                    for j in 0..8 {
-                        if new_position.contains(&vec![i, j]) {
-                            if self.board[j as usize][i as usize] != None {
-                                if self.board[j as usize][i as usize].unwrap().color != own_color {
-                                    new_new_position.push(vec![i, j]);
-                                }
-                            } else {
-                                new_new_position.push(vec![i, j]);
-                            }
-                        }
+                /* Adds the diagonal captures, where the en passant square can be captured
+                if the last move was a double step by an opposite pawn that ended up right
+                beside this one */
+                let mut captures = attacked_squares & self.bitboards.color(opposite_color);
+                if let Some(target) = self.en_passant {
+                    let passed_pawn = Square::new(target.file(), square.rank()).and_then(|s| self.piece_at(s));
+                    let opposite_pawn = Some(Piece {
+                        piecetype: PieceType::Pawn,
+                        color: opposite_color,
+                    });
+                    if passed_pawn == opposite_pawn {
+                        captures |= self.bitboards.attacks(square) & (1 << target.index());
                     }
                 }
-            } else {
-                new_new_position = new_position
+                possible_squares.extend(bitboard::squares(captures));
             }
-            //println!("{:?}", new_new_position);
-
-            // Comment out the for and if below if you don't want corpses
-            /*for i in 0..8 {
-                for j in 0..8 {
-                    if new_new_position.contains(&vec![i, j]) {
-                        self.board[j as usize][i as usize] = Some(Piece {
-                            piecetype: PieceType::Corpse,
-                            color: Color::White,
-                        });
+            PieceType::King => {
+                possible_squares.extend(bitboard::squares(attacked_squares));
+
+                /* Adds castling if the rights remain, the squares between king and rook are
+                empty and the king doesn't start in, pass through or land on an attacked square */
+                let home_rank = if own_color == Color::White { 0 } else { 7 };
+                let (kingside, queenside) = self.castling_rights.for_color(own_color);
+                let on_home_rank = |file: u8| Square::new(file, home_rank).unwrap();
+                let own_rook = Some(Piece {
+                    piecetype: PieceType::Rook,
+                    color: own_color,
+                });
+                let empty = |files: &[u8]| {
+                    files.iter().all(|f| self.piece_at(on_home_rank(*f)).is_none())
+                };
+                let safe = |files: &[u8]| {
+                    files
+                        .iter()
+                        .all(|f| !Game::is_square_attacked(self, on_home_rank(*f), opposite_color))
+                };
+                if square == on_home_rank(4) && safe(&[4]) {
+                    if kingside
+                        && self.piece_at(on_home_rank(7)) == own_rook
+                        && empty(&[5, 6])
+                        && safe(&[5, 6])
+                    {
+                        possible_squares.push(on_home_rank(6));
+                    }
+                    if queenside
+                        && self.piece_at(on_home_rank(0)) == own_rook
+                        && empty(&[1, 2, 3])
+                        && safe(&[3, 2])
+                    {
+                        possible_squares.push(on_home_rank(2));
                     }
                 }
             }
+            // Knights and the sliding pieces move exactly like they attack
+            _ => possible_squares.extend(bitboard::squares(attacked_squares)),
+        }
 
-            println!("{:?}", new_new_position);
-            if new_new_position.iter().count() > 0 {
-                Game::print(&self)
-            }*/
-
-            let converted_new_vector = Game::convert_vec_to_string(&new_new_position);
-            //println!("{:?}", converted_new_vector);
+        // Describes every square as a move, with what the move does besides moving the piece
+        let mut possible_moves = vec![];
+        for target in possible_squares {
+            let is_pawn = current_piecetype == PieceType::Pawn;
+            let en_passant = is_pawn && self.en_passant == Some(target);
+            let file_distance = target.file() as i8 - square.file() as i8;
+            let rank_distance = target.rank() as i8 - square.rank() as i8;
+            let flags = MoveFlags {
+                capture: self.piece_at(target).is_some() || en_passant,
+                en_passant,
+                castling: current_piecetype == PieceType::King && file_distance.abs() == 2,
+                double_pawn_push: is_pawn && rank_distance.abs() == 2,
+            };
+            let possible_move = Move {
+                flags,
+                ..Move::new(square, target)
+            };
 
             // Check for check
-            let mut even_newer_vector: Vec<Vec<i8>> = vec![];
-            let mut even_newer_converted_new_vector: Vec<String> = vec![];
-
-            if should_check {
-                for mut i in 0..converted_new_vector.iter().count() {
-                    let saved_boardstate = self.board;
-                    let stringposition = &Game::convert_vec_to_string(&vec![_position.to_vec()])[0];
-
-                    Game::make_move(
-                        self,
-                        &stringposition,
-                        converted_new_vector[i].to_string(),
-                        false,
-                    );
-
-                    let check = Game::check_check(self);
-                    if !check {
-                        even_newer_vector.push(new_new_position[i].clone());
-                        even_newer_converted_new_vector.push(converted_new_vector[i].clone());
-                    }
-                    self.board = saved_boardstate;
+            if should_check && Game::leaves_king_in_check(self, possible_move) {
+                continue;
+            }
+
+            // Lists a pawn move to the last rank once for every piece it can be promoted to
+            if is_pawn && (target.rank() == 0 || target.rank() == 7) {
+                for (_, piecetype) in PROMOTION_PIECES.iter() {
+                    possible_moves.push(Move {
+                        promotion: Some(*piecetype),
+                        ..possible_move
+                    });
                 }
             } else {
-                even_newer_vector = new_new_position;
-                even_newer_converted_new_vector = converted_new_vector;
+                possible_moves.push(possible_move);
             }
+        }
+
+        possible_moves
+    }
+}
 
-            return (even_newer_converted_new_vector, even_newer_vector);
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+// Copies the game with its moves, but not the observers, which belong to the original game
+impl Clone for Game {
+    fn clone(&self) -> Self {
+        Game {
+            state: self.state,
+            bitboards: self.bitboards,
+            color: self.color,
+            en_passant: self.en_passant,
+            castling_rights: self.castling_rights,
+            history: self.history.clone(),
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            draw_offer: self.draw_offer,
+            observers: vec![],
+            move_stack: self.move_stack.clone(),
+            redo_stack: self.redo_stack.clone(),
+            key: self.key,
         }
     }
 }
@@ -0,0 +1,56 @@
+use crate::Game;
+use crate::Move;
+
+impl Game {
+    /* Counts the move sequences of the given length that can be played from the current
+    position, which for the well-known test positions has to match the published counts
+    if the moves are generated right. The rules that end a game by draw or by agreement
+    aren't taken into account, only checkmate and stalemate */
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut game = self.clone();
+        Game::count_leaves(&mut game, depth)
+    }
+
+    /* Splits perft up by the first move, with the count for each legal move of the current
+    color, which tells which move is wrong when the total doesn't match another engine's */
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return vec![];
+        }
+        let mut game = self.clone();
+        Game::get_all_possible_moves(self, self.color)
+            .into_iter()
+            .map(|mv| {
+                let leaves = Game::count_leaves_after(&mut game, mv, depth - 1);
+                (mv, leaves)
+            })
+            .collect()
+    }
+
+    // The moves of the last ply are counted without being made
+    fn count_leaves(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = Game::get_all_possible_moves(self, self.color);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|mv| Game::count_leaves_after(self, mv, depth - 1))
+            .sum()
+    }
+
+    /* Makes the legal move with only what the moves after it depend on, which is much
+    faster than make_move, counts from there and puts everything back */
+    fn count_leaves_after(&mut self, mv: Move, depth: u32) -> u64 {
+        let before = (self.bitboards, self.color, self.en_passant, self.castling_rights, self.key);
+        Game::move_piece(self, mv);
+        let opposite_color = Game::opposite_color_func(self.color);
+        Game::change_keyed(self, |game| game.color = opposite_color);
+        let leaves = Game::count_leaves(self, depth);
+        (self.bitboards, self.color, self.en_passant, self.castling_rights, self.key) = before;
+        leaves
+    }
+}
@@ -0,0 +1,550 @@
+use crate::Color;
+use crate::FenError;
+use crate::Game;
+use crate::Move;
+use crate::SanError;
+use crate::START_FEN;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::mem;
+use std::time::Duration;
+use std::vec::IntoIter;
+
+// Lines of moves are broken before they get longer than this, like most programs do
+const PGN_LINE_LENGTH: usize = 80;
+
+/* The tags written before the moves. The ones of the seven tag roster that aren't
+given are written as "?" (or "????.??.??" for the date), and the Result tag is
+always taken from the game */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnTags {
+    pub event: Option<String>,
+    pub site: Option<String>,
+    // Written as "YYYY.MM.DD", with "??" for the parts that aren't known
+    pub date: Option<String>,
+    pub round: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    // Any other tags, which are written after the roster in the given order
+    pub custom: Vec<(String, String)>,
+}
+
+// A comment and the time left on the clock after a move, which are written in braces after it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnAnnotation {
+    pub comment: Option<String>,
+    pub clock: Option<Duration>,
+}
+
+impl PgnAnnotation {
+    // Returns the annotation as a PGN comment, or None if there is nothing to write
+    fn to_comment(&self) -> Option<String> {
+        let mut parts = vec![];
+        if let Some(clock) = self.clock {
+            let seconds = clock.as_secs();
+            parts.push(format!(
+                "[%clk {}:{:02}:{:02}]",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ));
+        }
+        // A comment ends at the first closing brace, so it can't have one in it
+        if let Some(comment) = &self.comment {
+            parts.push(comment.replace('}', ""));
+        }
+        if parts.is_empty() {
+            None
+        } else {
+            Some(format!("{{{}}}", parts.join(" ")))
+        }
+    }
+}
+
+// A move read from a PGN game, with everything that was written around it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnMove {
+    pub mv: Move,
+    // The move as it was written
+    pub san: String,
+    // Numeric annotation glyphs like $1, where suffixes like "!" are read as their glyph
+    pub nags: Vec<u8>,
+    // Comments before the move, which are only there for the first move of a line
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    // The time left on the clock after the move, from a [%clk h:mm:ss] command in a comment
+    pub clock: Option<Duration>,
+    // Other moves that could have been made instead, each starting with that move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+// A game read from PGN, where game is where the moves of the main line lead
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub tags: PgnTags,
+    pub moves: Vec<PgnMove>,
+    // "1-0", "0-1", "1/2-1/2" or "*", from the end of the moves or else the Result tag
+    pub result: String,
+    pub game: Game,
+}
+
+impl PgnGame {
+    // Returns the comments and clocks of the main line, as to_pgn takes them
+    pub fn annotations(&self) -> Vec<PgnAnnotation> {
+        self.moves
+            .iter()
+            .map(|pgn_move| PgnAnnotation {
+                comment: Some(pgn_move.comments.join(" ")).filter(|comment| !comment.is_empty()),
+                clock: pgn_move.clock,
+            })
+            .collect()
+    }
+}
+
+// What is wrong in a PGN file, and where
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnError {
+    // The game it's in, counting from 1
+    pub game: usize,
+    /* The ply of the move it happened at, counted like the move numbers so that 1 is
+    white's first move and 80 is black's 40th, also in a game that starts from a FEN */
+    pub ply: usize,
+    pub kind: PgnErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgnErrorKind {
+    // Text that can't be read, e.g. a comment or tag that is never closed
+    BadSyntax(String),
+    BadFen(FenError),
+    // An illegal or ambiguous move, or one that can't be read as SAN
+    BadMove(SanError),
+    // A variation that isn't closed, is closed without being opened, or comes before any move
+    BadVariation,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Game {}, ply {}: ", self.game, self.ply)?;
+        match &self.kind {
+            PgnErrorKind::BadSyntax(text) => write!(f, "can't read {}", text),
+            PgnErrorKind::BadFen(error) => write!(f, "{}", error),
+            PgnErrorKind::BadMove(error) => write!(f, "{}", error),
+            PgnErrorKind::BadVariation => write!(f, "the variation doesn't fit"),
+        }
+    }
+}
+
+impl Error for PgnError {}
+
+// The parts of PGN text that the games are read from
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    StartVariation,
+    EndVariation,
+    Result(String),
+    San(String),
+    // Text that isn't PGN, which stops the reading when it's reached
+    Unreadable(String),
+}
+
+// Characters that moves, move numbers and results are made of
+fn is_symbol(letter: char) -> bool {
+    letter.is_ascii_alphanumeric() || "_+#=:-/!?".contains(letter)
+}
+
+// Returns where the letters from start stop being kept, or the end if they never do
+fn end_of(letters: &[char], start: usize, keep: impl Fn(char) -> bool) -> usize {
+    (start..letters.len()).find(|&i| !keep(letters[i])).unwrap_or(letters.len())
+}
+
+/* Splits the text into tokens, where move numbers and periods are skipped, and suffixes
+like "!?" become NAGs of their own */
+fn tokenize(pgn: &str) -> Vec<Token> {
+    let letters: Vec<char> = pgn.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < letters.len() {
+        let letter = letters[i];
+        let rest_of_line = |start: usize| {
+            let end = end_of(&letters, start, |letter| letter != '\n');
+            (letters[start..end].iter().collect::<String>(), end)
+        };
+        match letter {
+            // A line starting with % is skipped, and ; starts a comment to the end of the line
+            '%' if i == 0 || letters[i - 1] == '\n' => i = rest_of_line(i).1,
+            ';' => {
+                let (comment, end) = rest_of_line(i + 1);
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                i = end;
+            }
+            '{' => match (i..letters.len()).find(|&j| letters[j] == '}') {
+                Some(end) => {
+                    let comment: String = letters[i + 1..end].iter().collect();
+                    let words: Vec<&str> = comment.split_whitespace().collect();
+                    tokens.push(Token::Comment(words.join(" ")));
+                    i = end + 1;
+                }
+                None => {
+                    tokens.push(Token::Unreadable("a comment that is never closed".to_string()));
+                    break;
+                }
+            },
+            '[' => match read_tag(&letters, i) {
+                Some((name, value, end)) => {
+                    tokens.push(Token::Tag(name, value));
+                    i = end;
+                }
+                None => {
+                    tokens.push(Token::Unreadable(format!("the tag \"{}\"", rest_of_line(i).0)));
+                    break;
+                }
+            },
+            '(' => {
+                tokens.push(Token::StartVariation);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::EndVariation);
+                i += 1;
+            }
+            '*' => {
+                tokens.push(Token::Result("*".to_string()));
+                i += 1;
+            }
+            '$' => {
+                let end = end_of(&letters, i + 1, |letter| letter.is_ascii_digit());
+                let digits: String = letters[i + 1..end].iter().collect();
+                match digits.parse() {
+                    Ok(nag) => tokens.push(Token::Nag(nag)),
+                    Err(_) => {
+                        tokens.push(Token::Unreadable(format!("the NAG \"${}\"", digits)));
+                        break;
+                    }
+                }
+                i = end;
+            }
+            letter if letter == '.' || letter.is_whitespace() => i += 1,
+            letter if is_symbol(letter) => {
+                let end = end_of(&letters, i, is_symbol);
+                let symbol: String = letters[i..end].iter().collect();
+                i = end;
+                if symbol.chars().all(|letter| letter.is_ascii_digit()) {
+                    continue;
+                }
+                if ["1-0", "0-1", "1/2-1/2"].contains(&symbol.as_str()) {
+                    tokens.push(Token::Result(symbol));
+                    continue;
+                }
+                let san = symbol.trim_end_matches(['!', '?']);
+                let nag = match &symbol[san.len()..] {
+                    "" => None,
+                    "!" => Some(1),
+                    "?" => Some(2),
+                    "!!" => Some(3),
+                    "??" => Some(4),
+                    "!?" => Some(5),
+                    "?!" => Some(6),
+                    suffix => {
+                        tokens.push(Token::Unreadable(format!("the annotation \"{}\"", suffix)));
+                        break;
+                    }
+                };
+                tokens.push(Token::San(san.to_string()));
+                tokens.extend(nag.map(Token::Nag));
+            }
+            letter => {
+                tokens.push(Token::Unreadable(format!("'{}'", letter)));
+                break;
+            }
+        }
+    }
+    tokens
+}
+
+/* Reads a tag pair like [White "Morphy"] starting at the bracket, and returns the
+name, the value and where the tag ends, or None if it isn't a tag pair */
+fn read_tag(letters: &[char], start: usize) -> Option<(String, String, usize)> {
+    let mut i = start + 1;
+    let skip_whitespace = |mut i: usize| {
+        while i < letters.len() && letters[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    i = skip_whitespace(i);
+    let mut name = String::new();
+    while i < letters.len() && (letters[i].is_ascii_alphanumeric() || letters[i] == '_') {
+        name.push(letters[i]);
+        i += 1;
+    }
+    i = skip_whitespace(i);
+    if name.is_empty() || letters.get(i) != Some(&'"') {
+        return None;
+    }
+
+    // A backslash lets the value have quotes and backslashes in it
+    let mut value = String::new();
+    i += 1;
+    loop {
+        match letters.get(i)? {
+            '"' => break,
+            '\\' => {
+                value.push(*letters.get(i + 1)?);
+                i += 2;
+            }
+            letter => {
+                value.push(*letter);
+                i += 1;
+            }
+        }
+    }
+    i = skip_whitespace(i + 1);
+    if letters.get(i) != Some(&']') {
+        return None;
+    }
+    Some((name, value, i + 1))
+}
+
+// Takes a [%clk h:mm:ss] command out of a comment, and returns what's left of it and the time
+fn split_clock(comment: &str) -> (String, Option<Duration>) {
+    let start = match comment.find("[%clk ") {
+        Some(start) => start,
+        None => return (comment.to_string(), None),
+    };
+    let end = match comment[start..].find(']') {
+        Some(end) => start + end,
+        None => return (comment.to_string(), None),
+    };
+    let mut seconds = 0.0;
+    for part in comment[start + 6..end].trim().split(':') {
+        match part.parse::<f64>() {
+            Ok(number) if number >= 0.0 => seconds = seconds * 60.0 + number,
+            _ => return (comment.to_string(), None),
+        }
+    }
+    // A time too long for a Duration, or infinite, isn't a clock and stays in the comment
+    let clock = match Duration::try_from_secs_f64(seconds) {
+        Ok(clock) => clock,
+        Err(_) => return (comment.to_string(), None),
+    };
+    let rest = format!("{} {}", &comment[..start], &comment[end + 1..]);
+    (rest.trim().to_string(), Some(clock))
+}
+
+/* Reads the moves of a line, making them on the game, until the line ends. The main
+line (at depth 0) ends at a result, a tag or the end of the text, and a variation at
+its closing parenthesis. Every variation is read from the position before the move
+it's an alternative to */
+fn read_line(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    game: &mut Game,
+    depth: usize,
+    number: usize,
+) -> Result<Vec<PgnMove>, PgnError> {
+    let mut moves: Vec<PgnMove> = vec![];
+    let mut before_last: Option<Game> = None;
+    let mut comments_before = vec![];
+    loop {
+        let ply = (game.fullmove_number as usize).saturating_sub(1) * 2
+            + if game.color == Color::White { 1 } else { 2 };
+        let error = |kind| PgnError { game: number, ply, kind };
+        match tokens.peek() {
+            None | Some(Token::Tag(..)) | Some(Token::Result(_)) if depth > 0 => {
+                return Err(error(PgnErrorKind::BadVariation))
+            }
+            None | Some(Token::Tag(..)) | Some(Token::Result(_)) => return Ok(moves),
+            _ => {}
+        }
+
+        match tokens.next().unwrap() {
+            Token::San(san) => {
+                let mv = Game::parse_san(game, &san)
+                    .map_err(|san_error| error(PgnErrorKind::BadMove(san_error)))?;
+                before_last = Some(game.clone());
+                if Game::make_move(game, mv, true).is_err() {
+                    return Err(error(PgnErrorKind::BadMove(SanError::IllegalMove(san))));
+                }
+                moves.push(PgnMove {
+                    mv,
+                    san,
+                    nags: vec![],
+                    comments_before: mem::take(&mut comments_before),
+                    comments: vec![],
+                    clock: None,
+                    variations: vec![],
+                });
+            }
+            // A NAG before the first move has nothing to say anything about
+            Token::Nag(nag) => {
+                if let Some(last) = moves.last_mut() {
+                    last.nags.push(nag);
+                }
+            }
+            Token::Comment(comment) => match moves.last_mut() {
+                Some(last) => {
+                    let (comment, clock) = split_clock(&comment);
+                    if clock.is_some() {
+                        last.clock = clock;
+                    }
+                    if !comment.is_empty() {
+                        last.comments.push(comment);
+                    }
+                }
+                None => comments_before.push(comment),
+            },
+            Token::StartVariation => {
+                let mut position = before_last
+                    .clone()
+                    .ok_or_else(|| error(PgnErrorKind::BadVariation))?;
+                let variation = read_line(tokens, &mut position, depth + 1, number)?;
+                moves.last_mut().unwrap().variations.push(variation);
+            }
+            Token::EndVariation if depth > 0 => return Ok(moves),
+            Token::EndVariation => return Err(error(PgnErrorKind::BadVariation)),
+            Token::Unreadable(text) => return Err(error(PgnErrorKind::BadSyntax(text))),
+            Token::Tag(..) | Token::Result(_) => unreachable!(),
+        }
+    }
+}
+
+// Writes a tag pair, where backslashes and quotes in the value are escaped
+fn tag_pair(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, value)
+}
+
+impl Game {
+    /* Writes the moves made so far as a game in Portable Game Notation, with the moves
+    in SAN. annotations[i] is written after the i-th move, starting from 0, and moves
+    past the end of annotations don't get any. A game that didn't start from the
+    initial position gets SetUp and FEN tags with the position it started from */
+    pub fn to_pgn(&self, tags: &PgnTags, annotations: &[PgnAnnotation]) -> String {
+        /* Takes back every move on a copy, which can then make them again one by one,
+        where moves that were already taken back in the game aren't part of it */
+        let mut game = self.clone();
+        game.redo_stack.clear();
+        while Game::undo_move(&mut game).is_some() {}
+
+        let mut pgn = String::new();
+        let roster = [
+            ("Event", &tags.event, "?"),
+            ("Site", &tags.site, "?"),
+            ("Date", &tags.date, "????.??.??"),
+            ("Round", &tags.round, "?"),
+            ("White", &tags.white, "?"),
+            ("Black", &tags.black, "?"),
+        ];
+        for (name, value, unknown) in roster.iter() {
+            pgn.push_str(&tag_pair(name, value.as_deref().unwrap_or(unknown)));
+        }
+        let result = self.state.result_string();
+        pgn.push_str(&tag_pair("Result", result));
+        let start_fen = Game::to_fen(&game);
+        if start_fen != START_FEN {
+            pgn.push_str(&tag_pair("SetUp", "1"));
+            pgn.push_str(&tag_pair("FEN", &start_fen));
+        }
+        for (name, value) in tags.custom.iter() {
+            pgn.push_str(&tag_pair(name, value));
+        }
+        pgn.push('\n');
+
+        /* A black move gets its number with "..." when it doesn't come right after the
+        white move, which is at the start and after a comment */
+        let mut tokens = vec![];
+        let mut needs_number = true;
+        let mut ply = 0;
+        while let Some(&(mv, _)) = game.redo_stack.last() {
+            let san = match Game::to_san(&game, mv) {
+                Ok(san) => san,
+                Err(_) => break,
+            };
+            if game.color == Color::White {
+                tokens.push(format!("{}.", game.fullmove_number));
+            } else if needs_number {
+                tokens.push(format!("{}...", game.fullmove_number));
+            }
+            tokens.push(san);
+            Game::redo_move(&mut game);
+
+            let comment = annotations.get(ply).and_then(PgnAnnotation::to_comment);
+            needs_number = comment.is_some();
+            tokens.extend(comment);
+            ply += 1;
+        }
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_LENGTH {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+
+    /* Reads every game in a PGN file, with its tags, comments, NAGs and variations. The
+    moves are checked to be legal, and a game starts from its FEN tag if it has one. The
+    error tells the game and ply where something can't be read */
+    pub fn from_pgn(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+        let mut tokens = tokenize(pgn).into_iter().peekable();
+        let mut games = vec![];
+        while tokens.peek().is_some() {
+            let number = games.len() + 1;
+            let mut tags = PgnTags::default();
+            let mut result_tag = None;
+            let mut game = Game::new();
+            while let Some(Token::Tag(..)) = tokens.peek() {
+                let (name, value) = match tokens.next() {
+                    Some(Token::Tag(name, value)) => (name, value),
+                    _ => unreachable!(),
+                };
+                // Tags of the roster that aren't known are left out, like to_pgn takes them
+                let known = Some(value.clone()).filter(|value| value != "?" && value != "????.??.??");
+                match name.as_str() {
+                    "Event" => tags.event = known,
+                    "Site" => tags.site = known,
+                    "Date" => tags.date = known,
+                    "Round" => tags.round = known,
+                    "White" => tags.white = known,
+                    "Black" => tags.black = known,
+                    "Result" => result_tag = Some(value),
+                    "SetUp" => {}
+                    "FEN" => {
+                        game = Game::from_fen(&value).map_err(|fen_error| PgnError {
+                            game: number,
+                            ply: 0,
+                            kind: PgnErrorKind::BadFen(fen_error),
+                        })?
+                    }
+                    _ => tags.custom.push((name, value)),
+                }
+            }
+
+            let moves = read_line(&mut tokens, &mut game, 0, number)?;
+            let result = match tokens.peek() {
+                Some(Token::Result(result)) => {
+                    let result = result.clone();
+                    tokens.next();
+                    result
+                }
+                _ => result_tag.unwrap_or_else(|| "*".to_string()),
+            };
+            games.push(PgnGame { tags, moves, result, game });
+        }
+        Ok(games)
+    }
+}
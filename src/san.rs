@@ -0,0 +1,193 @@
+use crate::Game;
+use crate::Move;
+use crate::MoveError;
+use crate::PieceType;
+use crate::Square;
+use std::error::Error;
+use std::fmt;
+
+// Why a move in SAN couldn't be read, with the move as it was written
+#[derive(Clone, Debug, PartialEq)]
+pub enum SanError {
+    // The text isn't a move in SAN
+    BadNotation(String),
+    // No legal move of the current color fits the move
+    IllegalMove(String),
+    // More than one legal move fits, e.g. "Nd2" when both knights can go there
+    AmbiguousMove(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::BadNotation(san) => write!(f, "\"{}\" isn't a move in SAN", san),
+            SanError::IllegalMove(san) => write!(f, "{} isn't a legal move", san),
+            SanError::AmbiguousMove(san) => {
+                write!(f, "{} could be more than one move, tell the pieces apart", san)
+            }
+        }
+    }
+}
+
+impl Error for SanError {}
+
+impl Game {
+    /* Writes a move of the current color in Standard Algebraic Notation, e.g. "Nbd7",
+    "exd8=Q+" or "O-O", or returns why it can't be made like make_move would */
+    pub fn to_san(&self, mv: Move) -> Result<String, MoveError> {
+        let mut after = self.clone();
+        let outcome = Game::make_move(&mut after, mv, true)?;
+        let mv = outcome.mv;
+        let piece = self.piece_at(mv.from).unwrap();
+        let square = mv.to.to_string();
+
+        let mut san = if mv.flags.castling {
+            if mv.to.file() == 6 { "O-O" } else { "O-O-O" }.to_string()
+        } else if piece.piecetype == PieceType::Pawn {
+            // A pawn is only written with its file when it captures
+            let mut san = String::new();
+            if mv.flags.capture {
+                san.push_str(&mv.from.to_string()[..1]);
+                san.push('x');
+            }
+            san.push_str(&square);
+            if let Some(piecetype) = mv.promotion {
+                san.push('=');
+                san.push(piecetype.letter());
+            }
+            san
+        } else {
+            let mut san = piece.piecetype.letter().to_string();
+            san.push_str(&Game::disambiguation(self, mv));
+            if mv.flags.capture {
+                san.push('x');
+            }
+            san.push_str(&square);
+            san
+        };
+
+        if outcome.check {
+            let mated = Game::get_all_possible_moves(&after, after.color).is_empty();
+            san.push(if mated { '#' } else { '+' });
+        }
+        Ok(san)
+    }
+
+    /* Returns what has to be written after the piece letter to tell the move apart from
+    moves of other pieces of the same type to the same square. The file is enough if
+    none of them stand on it, otherwise the rank, and if neither is enough, both */
+    fn disambiguation(&self, mv: Move) -> String {
+        let piecetype = self.piece_at(mv.from).map(|piece| piece.piecetype);
+        let others: Vec<Move> = Game::get_all_possible_moves(self, self.color)
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && self.piece_at(other.from).map(|piece| piece.piecetype) == piecetype
+            })
+            .collect();
+
+        let from = mv.from.to_string();
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|other| other.from.file() != mv.from.file()) {
+            from[..1].to_string()
+        } else if others.iter().all(|other| other.from.rank() != mv.from.rank()) {
+            from[1..].to_string()
+        } else {
+            from
+        }
+    }
+
+    /* Reads a move of the current color in SAN, e.g. "Nbd7" or "exd8=N+", and returns the
+    only legal move it can be. Check and mate suffixes and annotations like "!?" aren't
+    needed, and castling can also be written with zeros */
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let bad_notation = || SanError::BadNotation(san.to_string());
+        let text = san.trim().trim_end_matches(|letter| "+#!?".contains(letter));
+
+        let legal_moves = Game::get_all_possible_moves(self, self.color);
+        let castling_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        let candidates: Vec<Move> = if let Some(file) = castling_file {
+            legal_moves
+                .into_iter()
+                .filter(|mv| mv.flags.castling && mv.to.file() == file)
+                .collect()
+        } else {
+            let mut letters: Vec<char> = text.chars().collect();
+
+            // The piece letter is uppercase, so "b" is always a pawn on the b-file
+            let piecetype = match letters.first() {
+                Some(letter) if letter.is_ascii_uppercase() => {
+                    let piecetype = PieceType::from_letter(*letter).ok_or_else(bad_notation)?;
+                    letters.remove(0);
+                    piecetype
+                }
+                _ => PieceType::Pawn,
+            };
+
+            // The promotion comes last, with or without "="
+            let promotion = match letters.last() {
+                Some(letter) if letter.is_ascii_uppercase() => {
+                    let promotion = PieceType::from_letter(*letter).ok_or_else(bad_notation)?;
+                    letters.pop();
+                    if letters.last() == Some(&'=') {
+                        letters.pop();
+                    }
+                    Some(promotion)
+                }
+                _ => None,
+            };
+
+            if letters.len() < 2 {
+                return Err(bad_notation());
+            }
+            let to: Square = letters
+                .split_off(letters.len() - 2)
+                .into_iter()
+                .collect::<String>()
+                .parse()
+                .map_err(|_| bad_notation())?;
+            let capture = letters.last() == Some(&'x');
+            if capture {
+                letters.pop();
+            }
+
+            // What's left tells the piece apart by its file, its rank or both
+            let mut from_file = None;
+            let mut from_rank = None;
+            for letter in letters {
+                match letter {
+                    'a'..='h' if from_file.is_none() && from_rank.is_none() => {
+                        from_file = Some(letter as u8 - b'a')
+                    }
+                    '1'..='8' if from_rank.is_none() => from_rank = Some(letter as u8 - b'1'),
+                    _ => return Err(bad_notation()),
+                }
+            }
+
+            legal_moves
+                .into_iter()
+                .filter(|mv| {
+                    self.piece_at(mv.from).map(|piece| piece.piecetype) == Some(piecetype)
+                        && mv.to == to
+                        && mv.promotion == promotion
+                        && !mv.flags.castling
+                        && (!capture || mv.flags.capture)
+                        && from_file.map_or(true, |file| mv.from.file() == file)
+                        && from_rank.map_or(true, |rank| mv.from.rank() == rank)
+                })
+                .collect()
+        };
+
+        match candidates.len() {
+            0 => Err(SanError::IllegalMove(san.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanError::AmbiguousMove(san.to_string())),
+        }
+    }
+}
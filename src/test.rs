@@ -4,14 +4,46 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::lib::Game;
-    use crate::lib::GameState;
-    use crate::lib::Piece;
-    use crate::lib::PieceType;
-    use crate::lib::Color;
+    use crate::Game;
+    use crate::GameState;
+    use crate::Piece;
+    use crate::PieceType;
+    use crate::Color;
+    use crate::CastlingRights;
+    use crate::GameResult;
+    use crate::DrawReason;
+    use crate::Move;
+    use crate::MoveError;
+    use crate::MoveFlags;
+    use crate::GameEvent;
+    use crate::GameObserver;
+    use std::sync::{Arc, Mutex};
+    use crate::Square;
+    use crate::FenError;
+    use crate::START_FEN;
+    use crate::SanError;
+    use crate::PgnTags;
+    use crate::PgnAnnotation;
+    use crate::PgnError;
+    use crate::PgnErrorKind;
+    use crate::NULL_MOVE_UCI;
+    use crate::EpdRecord;
+    use crate::EpdError;
+    use crate::Bitboards;
+    use std::time::Duration;
 
     // cargo test -- --nocapture --test-threads=1
 
+    fn sq(name: &str) -> Square {
+        name.parse().unwrap()
+    }
+
+    // Returns the squares the piece on the square can move to, written like "e4"
+    fn destinations(game: &Game, square: &str) -> Vec<String> {
+        let possible_moves = Game::get_possible_moves(game, sq(square), true);
+        possible_moves.iter().map(|mv| mv.to.to_string()).collect()
+    }
+
     // check test framework
     #[test]
     fn it_works() {
@@ -29,207 +61,1350 @@ mod tests {
         assert_eq!(game.get_game_state(), GameState::InProgress);
     }
 
+    #[test]
+    fn squares() {
+        let square = sq("E4");
+        assert_eq!((square.file(), square.rank()), (4, 3));
+        assert_eq!(square.to_string(), "e4");
+        assert_eq!(Square::new(4, 3), Some(square));
+        assert_eq!(square.offset(3, 4), Some(sq("h8")));
+        assert_eq!(square.offset(4, 0), None);
+        assert_eq!(Square::new(8, 0), None);
+        assert_eq!(Square::all().count(), 64);
+        for bad in ["", "e", "e9", "i4", "e44", "4e"] {
+            assert!(bad.parse::<Square>().is_err());
+        }
+    }
+
     #[test]
     fn move_a_pawn() {
         let mut game = Game::new();
-        Game::make_move(&mut game, &"A2".to_string(), "A3".to_string(), true);
-        let a_boardstate =    [
-            [
-                Some(Piece {
-                    piecetype: PieceType::Rook,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Knight,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Bishop,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Queen,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::King,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Bishop,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Knight,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Rook,
-                    color: Color::Black,
-                }),
-            ],
-            [
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::Black,
-                }),
-            ],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [None, None, None, None, None, None, None, None],
-            [Some(Piece {
-                piecetype: PieceType::Pawn,
-                color: Color::White,
-            }), None, None, None, None, None, None, None],
-            [
-                None,
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                }),
-            ],
-            [
-                Some(Piece {
-                    piecetype: PieceType::Rook,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Knight,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Bishop,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Queen,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::King,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Bishop,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Knight,
-                    color: Color::White,
-                }),
-                Some(Piece {
-                    piecetype: PieceType::Rook,
-                    color: Color::White,
-                }),
-            ],
-        ];
+        Game::make_move(&mut game, Move::new(sq("a2"), sq("a3")), true).unwrap();
         Game::print(&game);
-        assert_eq!(game.board, a_boardstate);
+        assert_eq!(Game::to_fen(&game), "rnbqkbnr/pppppppp/8/8/8/P7/1PPPPPPP/RNBQKBNR b KQkq - 0 1");
     }
 
     #[test]
     fn test_promotion() {
-        let mut game = Game::new();
-        game.board[0][0] = 
-                Some(Piece {
-                    piecetype: PieceType::Pawn,
-                    color: Color::White,
-                });
-        Game::set_promotion(&mut game, PieceType::Queen);
-        assert_eq!(game.board[0][0], Some(Piece {
-            piecetype: PieceType::Queen,
+        let mut game = Game::from_fen("7k/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+
+        let possible_moves = Game::get_possible_moves(&game, sq("a7"), true);
+        let promotions: Vec<_> = possible_moves.iter().map(|mv| (mv.to, mv.promotion)).collect();
+        assert_eq!(vec![
+            (sq("a8"), Some(PieceType::Queen)),
+            (sq("a8"), Some(PieceType::Rook)),
+            (sq("a8"), Some(PieceType::Bishop)),
+            (sq("a8"), Some(PieceType::Knight)),
+        ], promotions);
+
+        // The piece to promote to has to be given, and has to be one of the four above
+        let error = Game::make_move(&mut game, Move::new(sq("a7"), sq("a8")), true);
+        assert_eq!(error, Err(MoveError::InvalidPromotion));
+        let error = Game::make_move(&mut game, Move::with_promotion(sq("a7"), sq("a8"), PieceType::King), true);
+        assert_eq!(error, Err(MoveError::InvalidPromotion));
+        assert_eq!(game.piece_at(sq("a7")), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }));
+        assert_eq!(game.color, Color::White);
+
+        let outcome = Game::make_move(&mut game, Move::with_promotion(sq("a7"), sq("a8"), PieceType::Knight), true).unwrap();
+        assert_eq!(outcome.promotion, Some(PieceType::Knight));
+        assert_eq!(game.piece_at(sq("a8")), Some(Piece {
+            piecetype: PieceType::Knight,
             color: Color::White,
         }));
+        assert_eq!(game.piece_at(sq("a7")), None);
     }
 
     #[test]
-    fn test_check() {
+    fn promotion_only_on_the_last_row() {
+        let mut game = Game::new();
+        let error = Game::make_move(&mut game, Move::with_promotion(sq("e2"), sq("e4"), PieceType::Queen), true);
+        assert_eq!(error, Err(MoveError::InvalidPromotion));
+        assert_eq!(game.piece_at(sq("e2")), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }));
+        assert_eq!(game.color, Color::White);
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut game = Game::new();
+        assert_eq!("e2 e9".parse::<Move>(), Err(MoveError::BadNotation("e2 e9".to_string())));
+        assert_eq!("a7 a8k".parse::<Move>(), Err(MoveError::BadNotation("a7 a8k".to_string())));
+        assert_eq!("e2e4".parse::<Move>(), Ok(Move::new(sq("e2"), sq("e4"))));
+        assert_eq!("a7 A8q".parse(), Ok(Move::with_promotion(sq("a7"), sq("a8"), PieceType::Queen)));
+
+        let mut play = |from: &str, to: &str| Game::make_move(&mut game, Move::new(sq(from), sq(to)), true);
+        assert_eq!(play("e4", "e5"), Err(MoveError::EmptySquare(sq("e4"))));
+        assert_eq!(play("e7", "e5"), Err(MoveError::WrongSide(Color::White)));
+        assert_eq!(play("a1", "a3"), Err(MoveError::BlockedPath));
+        assert_eq!(play("b1", "b3"), Err(MoveError::IllegalMove));
+        assert_eq!(play("e1", "g1"), Err(MoveError::BlockedPath));
+        play("e2", "e4").unwrap();
+        play("d7", "d5").unwrap();
+        play("e1", "e2").unwrap();
+        play("d8", "d7").unwrap();
+        play("e4", "d5").unwrap();
+        play("d7", "b5").unwrap();
+
+        // The queen on b5 gives check, which a2 a3 does nothing about
+        assert_eq!(play("a2", "a3"), Err(MoveError::LeavesKingInCheck));
+        assert_eq!(game.color, Color::White);
+    }
+
+    #[test]
+    fn move_outcomes() {
+        let mut game = Game::new();
+        let outcome = Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        assert!(outcome.mv.flags.double_pawn_push);
+        assert_eq!((outcome.captured, outcome.check), (None, false));
+        assert_eq!(outcome.state, GameState::InProgress);
+
+        Game::make_move(&mut game, Move::new(sq("d7"), sq("d5")), true).unwrap();
+        let outcome = Game::make_move(&mut game, Move::new(sq("e4"), sq("d5")), true).unwrap();
+        assert!(outcome.mv.flags.capture);
+        assert_eq!(outcome.captured, Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }));
+
+        Game::make_move(&mut game, Move::new(sq("e7"), sq("e6")), true).unwrap();
+        let outcome = Game::make_move(&mut game, Move::new(sq("f1"), sq("b5")), true).unwrap();
+        assert!(outcome.check);
+        assert_eq!(outcome.state, GameState::Check);
+        assert_eq!(outcome.game_over(), None);
+
+        let mut game = Game::new();
+        for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4")] {
+            Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+        }
+        let outcome = Game::make_move(&mut game, Move::new(sq("d8"), sq("h4")), true).unwrap();
+        assert_eq!(outcome.game_over(), Some(GameResult::Checkmate { winner: Color::Black }));
+    }
+
+    // Keeps every event it gets, where the test holds on to the other end of the list
+    struct EventLog(Arc<Mutex<Vec<GameEvent>>>);
+
+    impl GameObserver for EventLog {
+        fn on_event(&mut self, event: &GameEvent) {
+            self.0.lock().unwrap().push(*event);
+        }
+    }
+
+    #[test]
+    fn observer_gets_events() {
+        let mut game = Game::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        game.subscribe(Box::new(EventLog(events.clone())));
+
+        for (from, to) in [("e2", "e4"), ("f7", "f5"), ("e4", "f5"), ("g7", "g5")] {
+            Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+        }
+        let outcome = Game::make_move(&mut game, Move::new(sq("d1"), sq("h5")), true).unwrap();
+        let black_pawn = Piece { piecetype: PieceType::Pawn, color: Color::Black };
+        assert_eq!(events.lock().unwrap()[5..], [
+            GameEvent::MoveMade(outcome.mv),
+            GameEvent::Check(Color::Black),
+            GameEvent::GameOver(GameResult::Checkmate { winner: Color::White }),
+        ]);
+        assert_eq!(events.lock().unwrap()[2..4], [
+            GameEvent::MoveMade(Move {
+                flags: MoveFlags { capture: true, ..Default::default() },
+                ..Move::new(sq("e4"), sq("f5"))
+            }),
+            GameEvent::Capture { square: sq("f5"), piece: black_pawn },
+        ]);
+
+        // A game only ends once
+        let count = events.lock().unwrap().len();
+        Game::resign(&mut game, Color::Black);
+        assert_eq!(events.lock().unwrap().len(), count);
+
+        // Trying moves like the AI does, without changing color, doesn't send anything
+        let mut game = Game::new();
+        let events = Arc::new(Mutex::new(vec![]));
+        game.subscribe(Box::new(EventLog(events.clone())));
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), false).unwrap();
+        Game::resign(&mut game, Color::White);
+        assert_eq!(*events.lock().unwrap(), [
+            GameEvent::GameOver(GameResult::Resignation { winner: Color::Black }),
+        ]);
+    }
+
+    // Everything undo_move has to put back
+    fn snapshot(game: &Game) -> impl PartialEq + std::fmt::Debug {
+        (
+            game.board(),
+            game.color,
+            game.state,
+            game.en_passant,
+            game.castling_rights,
+            (game.halfmove_clock, game.fullmove_number),
+            game.history.clone(),
+            game.draw_offer,
+        )
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = Game::new();
+        assert_eq!(Game::undo_move(&mut game), None);
+        let start = snapshot(&game);
+
+        // Goes through castling, a double step, en passant and a capture that promotes
+        let moves = [
+            ("g1", "f3"), ("a7", "a5"), ("e2", "e4"), ("a5", "a4"), ("f1", "c4"), ("a8", "a5"),
+            ("e1", "g1"), ("b7", "b5"), ("b2", "b4"), ("a4", "b3"), ("e4", "e5"), ("d7", "d5"),
+            ("e5", "d6"), ("b3", "c2"), ("d6", "e7"), ("c2", "b1"),
+        ];
+        let mut snapshots = vec![];
+        for (from, to) in moves.iter() {
+            snapshots.push(snapshot(&game));
+            let mv = match Game::make_move(&mut game, Move::new(sq(from), sq(to)), true) {
+                Err(MoveError::InvalidPromotion) => Move::with_promotion(sq(from), sq(to), PieceType::Queen),
+                result => result.unwrap().mv,
+            };
+            if mv.promotion.is_some() {
+                Game::make_move(&mut game, mv, true).unwrap();
+            }
+        }
+        assert_eq!(game.piece_at(sq("b1")), Some(Piece { piecetype: PieceType::Queen, color: Color::Black }));
+        let end = snapshot(&game);
+        let made = Game::moves(&game);
+        assert_eq!(made.len(), moves.len());
+
+        for expected in snapshots.iter().rev() {
+            Game::undo_move(&mut game).unwrap();
+            assert_eq!(snapshot(&game), *expected);
+        }
+        assert_eq!(snapshot(&game), start);
+
+        for mv in made.iter() {
+            assert_eq!(Game::redo_move(&mut game).unwrap().mv, *mv);
+        }
+        assert_eq!(snapshot(&game), end);
+        assert!(Game::redo_move(&mut game).is_none());
+
+        // Making another move after undoing one leaves nothing to redo
+        Game::undo_move(&mut game);
+        Game::make_move(&mut game, Move::new(sq("h7"), sq("h6")), true).unwrap();
+        assert!(Game::redo_move(&mut game).is_none());
+    }
+
+    #[test]
+    fn undo_after_the_game_ended() {
         let mut game = Game::new();
-        game.board = [[Some(Piece { piecetype: PieceType::Rook, color: Color::Black }), None, Some(Piece { piecetype: PieceType::Bishop, color: Color::Black }), None, Some(Piece { piecetype: PieceType::King, color: Color::Black }), Some(Piece { piecetype: PieceType::Bishop, color: Color::Black }), Some(Piece { piecetype: PieceType::Knight, color: Color::Black }), None], [Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Queen, color: Color::White }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None], [None, None, Some(Piece { piecetype: PieceType::Knight, color: Color::Black }), None, None, None, None, None], [Some(Piece { piecetype: PieceType::Queen, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::Black }), Some(Piece { piecetype: PieceType::Pawn, color: Color::Black })], [None, Some(Piece { piecetype: PieceType::Rook, color: Color::Black }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None], [Some(Piece { piecetype: PieceType::Bishop, color: Color::White }), None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, None, None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White })], [None, Some(Piece { piecetype: PieceType::Rook, color: Color::White }), None, None, None, Some(Piece { piecetype: PieceType::Pawn, color: Color::White }), None, Some(Piece { piecetype: PieceType::Rook, color: Color::White })], [None, Some(Piece { piecetype: PieceType::Knight, color: Color::White }), None, Some(Piece { piecetype: PieceType::King, color: Color::White }), None, Some(Piece { piecetype: PieceType::Bishop, color: Color::White }), Some(Piece { piecetype: PieceType::Knight, color: Color::White }), None]];
+        for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+            Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+        }
+        assert!(game.state.is_game_over());
+        assert_eq!(Game::undo_move(&mut game), Some(Move::new(sq("d8"), sq("h4"))));
+        assert_eq!(game.state, GameState::InProgress);
+        assert_eq!(game.color, Color::Black);
+        Game::make_move(&mut game, Move::new(sq("b8"), sq("c6")), true).unwrap();
+    }
+
+    #[test]
+    fn redo_after_the_game_ended() {
+        let mut game = Game::new();
+        play(&mut game, &["e2 e4", "e7 e5"]);
+        Game::undo_move(&mut game);
+        Game::resign(&mut game, Color::Black);
+        let before = snapshot(&game);
+        assert!(Game::redo_move(&mut game).is_none());
+        assert_eq!(snapshot(&game), before);
+        assert_eq!(game.redo_stack.len(), 1);
+    }
+
+    #[test]
+    fn ai_search_keeps_the_moves_to_redo() {
+        let mut game = Game::new();
+        play(&mut game, &["e2 e4", "e7 e5"]);
+        Game::undo_move(&mut game);
+        let before = snapshot(&game);
+        assert!(Game::ai_best_move(&mut game).is_some());
+        assert_eq!(snapshot(&game), before);
+
+        // The AI tried moves of its own, but only the move that was taken back can be redone
+        assert_eq!(Game::redo_move(&mut game).unwrap().mv.to_string(), "e7e5");
+        assert!(Game::redo_move(&mut game).is_none());
+        assert!(Game::to_pgn(&game, &PgnTags::default(), &[]).contains("1. e4 e5 *"));
+    }
+
+    #[test]
+    fn test_check() {
+        let mut game = Game::from_fen("r1b1kbn1/pp2Qp2/2n5/q2p2pp/1r1PP1P1/B1P4P/1R3P1R/1N1K1BN1 b - - 0 1").unwrap();
         Game::print(&game);
-        game.color = Color::Black;
         let check = Game::check_check(&mut game);
-        assert_eq!(true, check);
+        assert!(check);
     }
 
     #[test]
     fn test_game_state() {
-        let mut game = Game::new();
+        let game = Game::new();
         assert_eq!(        game.state 
             , GameState::InProgress);
     }
 
     #[test]
     fn get_possible_moves_pawn() {
-        let mut game = Game::new();
-        let (irrelevant, possible_moves) = Game::get_possible_moves(&mut game, &vec![0, 1], true);
+        let game = Game::new();
+        let irrelevant = destinations(&game, "a7");
         println!("{:?}", irrelevant);
-        assert_eq!(vec!["A6".to_string(), "A5".to_string()], irrelevant);
+        assert_eq!(vec!["a6".to_string(), "a5".to_string()], irrelevant);
     }
 
     #[test]
     fn get_possible_moves_knight() {
-        let mut game = Game::new();
-        let (irrelevant, possible_moves) = Game::get_possible_moves(&mut game, &vec![1, 0], true);
+        let game = Game::new();
+        let irrelevant = destinations(&game, "b8");
         println!("{:?}", irrelevant);
-        assert_eq!(vec!["A6".to_string(), "C6".to_string()], irrelevant);
+        assert_eq!(vec!["a6".to_string(), "c6".to_string()], irrelevant);
     }
 
     #[test]
-    fn test_print() {
+    fn moves_are_generated_without_changing_the_game() {
+        let mut game = Game::new();
+        for (from, to) in [("e2", "e4"), ("d7", "d5"), ("e4", "e5"), ("f7", "f5")] {
+            Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+        }
+        let board = game.board();
+        let position = Game::get_position(&game);
+
+        // The game can be shared between threads that all look for moves at once
+        let game = &game;
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let threads: Vec<_> = Square::all()
+                .filter(|square| game.piece_at(*square).map(|piece| piece.color) == Some(Color::White))
+                .map(|square| scope.spawn(move || Game::get_possible_moves(game, square, true).len()))
+                .collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).collect()
+        });
+        assert_eq!(counts.iter().sum::<usize>(), 31);
+        assert_eq!(game.board(), board);
+        assert_eq!(Game::get_position(game), position);
+        assert_eq!(game.state, GameState::InProgress);
+    }
+
+    #[test]
+    fn all_possible_moves_keep_every_origin() {
+        let game = Game::new();
+        let moves = Game::get_all_possible_moves(&game, Color::White);
+        assert_eq!(moves.len(), 20);
+
+        // Both the pawn on a2 and the knight on b1 can go to a3
+        let to_a3: Vec<Square> = moves.iter().filter(|mv| mv.to == sq("a3")).map(|mv| mv.from).collect();
+        assert_eq!(to_a3, vec![sq("b1"), sq("a2")]);
+
+        // Every promotion is its own move, and moves that leave the king in check aren't there
+        let game = Game::from_fen("4r2k/1P6/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+        let moves = Game::get_all_possible_moves(&game, Color::White);
+        assert_eq!(moves.iter().filter(|mv| mv.from == sq("b7")).count(), 4);
+        assert_eq!(moves.iter().filter(|mv| mv.from == sq("e2")).count(), 6);
+        assert_eq!(moves.len(), 4 + 6 + 4);
+    }
+
+    #[test]
+    fn en_passant_capture() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("a7"), sq("a6")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e4"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("d7"), sq("d5")), true).unwrap();
+        assert_eq!(game.en_passant, Some(sq("d6")));
+
+        let possible_moves = destinations(&game, "e5");
+        assert!(possible_moves.contains(&"d6".to_string()));
+
+        Game::make_move(&mut game, Move::new(sq("e5"), sq("d6")), true).unwrap();
+        assert_eq!(game.piece_at(sq("d6")), Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: Color::White,
+        }));
+        assert_eq!(game.piece_at(sq("d5")), None);
+        assert_eq!(game.piece_at(sq("e5")), None);
+        assert_eq!(game.en_passant, None);
+    }
+
+    #[test]
+    fn en_passant_expires_after_one_move() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("a7"), sq("a6")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e4"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("d7"), sq("d5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("h2"), sq("h3")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("h7"), sq("h6")), true).unwrap();
+
+        let possible_moves = destinations(&game, "e5");
+        assert!(!possible_moves.contains(&"d6".to_string()));
+
+        let error = Game::make_move(&mut game, Move::new(sq("e5"), sq("d6")), true);
+        assert_eq!(error, Err(MoveError::IllegalMove));
+        assert_eq!(game.piece_at(sq("d5")), Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: Color::Black,
+        }));
+        assert_eq!(game.piece_at(sq("e5")), Some(Piece {
+            piecetype: PieceType::Pawn,
+            color: Color::White,
+        }));
+    }
+
+    #[test]
+    fn en_passant_not_allowed_when_pinned() {
+        let mut game = Game::from_fen("7k/2p5/8/KP5r/8/8/8/8 b - - 0 1").unwrap();
+        Game::make_move(&mut game, Move::new(sq("c7"), sq("c5")), true).unwrap();
+
+        // Taking en passant would leave both pawns off the fifth rank and the king in check
+        let possible_moves = destinations(&game, "b5");
+        assert_eq!(vec!["b6".to_string()], possible_moves);
+
+        game.set_piece(sq("h5"), None);
+        let possible_moves = destinations(&game, "b5");
+        assert_eq!(vec!["b6".to_string(), "c6".to_string()], possible_moves);
+    }
+
+    #[test]
+    fn castle_kingside() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e7"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("g1"), sq("f3")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("b8"), sq("c6")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("f1"), sq("c4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("f8"), sq("c5")), true).unwrap();
+
+        let possible_moves = destinations(&game, "e1");
+        assert_eq!(vec!["f1".to_string(), "e2".to_string(), "g1".to_string()], possible_moves);
+
+        Game::make_move(&mut game, Move::new(sq("e1"), sq("g1")), true).unwrap();
+        assert_eq!(game.piece_at(sq("g1")), Some(Piece { piecetype: PieceType::King, color: Color::White }));
+        assert_eq!(game.piece_at(sq("f1")), Some(Piece { piecetype: PieceType::Rook, color: Color::White }));
+        assert_eq!(game.piece_at(sq("h1")), None);
+        assert_eq!(game.castling_rights.for_color(Color::White), (false, false));
+        assert_eq!(game.castling_rights.for_color(Color::Black), (true, true));
+    }
+
+    #[test]
+    fn castle_queenside() {
+        let mut game = Game::from_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1").unwrap();
+
+        Game::make_move(&mut game, Move::new(sq("e8"), sq("c8")), true).unwrap();
+        assert_eq!(game.piece_at(sq("c8")), Some(Piece { piecetype: PieceType::King, color: Color::Black }));
+        assert_eq!(game.piece_at(sq("d8")), Some(Piece { piecetype: PieceType::Rook, color: Color::Black }));
+        assert_eq!(game.piece_at(sq("a8")), None);
+    }
+
+    #[test]
+    fn no_castling_through_or_out_of_check() {
+        // A bishop on a6 covers f1, so only queenside castling is possible
+        let game = Game::from_fen("4k3/8/b7/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let possible_moves = destinations(&game, "e1");
+        assert!(possible_moves.contains(&"c1".to_string()));
+        assert!(!possible_moves.contains(&"g1".to_string()));
+
+        // A rook on the e-file gives check, so castling isn't possible at all
+        let game = Game::from_fen("4k3/8/4r3/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let possible_moves = destinations(&game, "e1");
+        assert!(!possible_moves.contains(&"c1".to_string()));
+        assert!(!possible_moves.contains(&"g1".to_string()));
+    }
+
+    #[test]
+    fn castling_rights_revoked() {
+        let mut game = Game::from_fen("r3k3/8/8/8/8/8/8/R3K2R w KQq - 0 1").unwrap();
+
+        // Moving a rook away and back still gives up that side
+        Game::make_move(&mut game, Move::new(sq("h1"), sq("h2")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e8"), sq("d8")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("h2"), sq("h1")), true).unwrap();
+        assert_eq!(game.castling_rights, CastlingRights {
+            white_kingside: false,
+            white_queenside: true,
+            black_kingside: false,
+            black_queenside: false,
+        });
+        let possible_moves = destinations(&game, "e1");
+        assert!(!possible_moves.contains(&"g1".to_string()));
+
+        // Capturing a rook on its starting square takes away the right too
+        game.castling_rights = CastlingRights::all();
+        game.color = Color::White;
+        Game::make_move(&mut game, Move::new(sq("a1"), sq("a8")), true).unwrap();
+        assert!(!game.castling_rights.white_queenside);
+        assert!(!game.castling_rights.black_queenside);
+        assert!(game.castling_rights.white_kingside);
+    }
+
+    #[test]
+    fn fools_mate_is_checkmate() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("f2"), sq("f3")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e7"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("g2"), sq("g4")), true).unwrap();
+        let state = Game::make_move(&mut game, Move::new(sq("d8"), sq("h4")), true).unwrap().state;
+        assert_eq!(state, GameState::GameOver(GameResult::Checkmate { winner: Color::Black }));
+        assert!(Game::checkmate(&mut game));
+        assert!(!Game::stalemate(&mut game));
+    }
+
+    #[test]
+    fn stalemate_is_not_checkmate() {
+        let mut game = Game::from_fen("7k/5K2/8/6Q1/8/8/8/8 w - - 0 1").unwrap();
+
+        let state = Game::make_move(&mut game, Move::new(sq("g5"), sq("g6")), true).unwrap().state;
+        assert_eq!(state, GameState::GameOver(GameResult::Draw(DrawReason::Stalemate)));
+        assert!(Game::stalemate(&mut game));
+        assert!(!Game::checkmate(&mut game));
+
+        // No more moves can be made once the game has ended
+        let error = Game::make_move(&mut game, Move::new(sq("h8"), sq("g8")), true);
+        assert_eq!(error, Err(MoveError::GameOver));
+        assert_eq!(game.piece_at(sq("h8")), Some(Piece { piecetype: PieceType::King, color: Color::Black }));
+    }
+
+    #[test]
+    fn dead_positions() {
+        assert!(!Game::dead_position(&Game::new()));
+
+        let fixtures = [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", true),
+            // Two knights can't force checkmate, but a checkmate is still possible
+            ("4k3/8/8/8/8/8/8/1N2K1N1 w - - 0 1", false),
+            // Bishops on the same square color (c1 and f8) never cover the other color
+            ("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+            ("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/P7/4K3 w - - 0 1", false),
+        ];
+        for (fen, dead) in fixtures.iter() {
+            let game = Game::from_fen(fen).unwrap();
+            assert_eq!(Game::dead_position(&game), *dead, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn capturing_the_last_piece_ends_the_game() {
+        let mut game = Game::from_fen("4k3/4q3/8/8/8/8/4R3/4K3 b - - 0 1").unwrap();
+
+        // The king has to get out of check by taking the queen
+        let state = Game::make_move(&mut game, Move::new(sq("e7"), sq("e2")), true).unwrap().state;
+        assert_eq!(state, GameState::Check);
+        let state = Game::make_move(&mut game, Move::new(sq("e1"), sq("e2")), true).unwrap().state;
+        assert_eq!(state, GameState::GameOver(GameResult::Draw(DrawReason::DeadPosition)));
+    }
+
+    #[test]
+    fn threefold_repetition_can_be_claimed() {
+        let mut game = Game::new();
+        assert!(!Game::claim_draw(&mut game));
+
+        // Castling rights are part of the position, so this isn't a repetition of the start
+        for (from, to) in [("g1", "f3"), ("g8", "f6"), ("h1", "g1"), ("h8", "g8"), ("g1", "h1"), ("g8", "h8"), ("f3", "g1"), ("f6", "g8")] {
+            Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+        }
+        assert_eq!(game.board(), Game::new().board());
+        assert_eq!(Game::repetitions(&game), 1);
+
+        for _ in 0..2 {
+            for (from, to) in [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")] {
+                Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+            }
+        }
+        assert_eq!(Game::repetitions(&game), 3);
+        assert_eq!(game.state, GameState::InProgress);
+        assert!(Game::claim_draw(&mut game));
+        assert_eq!(game.state, GameState::GameOver(GameResult::Draw(DrawReason::ThreefoldRepetition)));
+    }
+
+    #[test]
+    fn fivefold_repetition_ends_the_game() {
+        let mut game = Game::new();
+        for _ in 0..4 {
+            for (from, to) in [("b1", "c3"), ("b8", "c6"), ("c3", "b1"), ("c6", "b8")] {
+                Game::make_move(&mut game, Move::new(sq(from), sq(to)), true).unwrap();
+            }
+        }
+        assert_eq!(Game::repetitions(&game), 5);
+        assert_eq!(game.state, GameState::GameOver(GameResult::Draw(DrawReason::FivefoldRepetition)));
+    }
+
+    #[test]
+    fn en_passant_only_counts_when_possible() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        assert_eq!(Game::get_position(&game).en_passant, None);
+
+        Game::make_move(&mut game, Move::new(sq("d7"), sq("d5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e4"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("f7"), sq("f5")), true).unwrap();
+        assert_eq!(Game::get_position(&game).en_passant, Some(sq("f6")));
+    }
+
+    #[test]
+    fn halfmove_clock_and_fullmove_number() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("g1"), sq("f3")), true).unwrap();
+        assert_eq!((game.halfmove_clock, game.fullmove_number), (1, 1));
+        Game::make_move(&mut game, Move::new(sq("d7"), sq("d5")), true).unwrap();
+        assert_eq!((game.halfmove_clock, game.fullmove_number), (0, 2));
+        Game::make_move(&mut game, Move::new(sq("f3"), sq("e5")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("b8"), sq("c6")), true).unwrap();
+        assert_eq!((game.halfmove_clock, game.fullmove_number), (2, 3));
+        Game::make_move(&mut game, Move::new(sq("e5"), sq("c6")), true).unwrap();
+        assert_eq!((game.halfmove_clock, game.fullmove_number), (0, 3));
+    }
+
+    #[test]
+    fn fifty_move_rule_can_be_claimed() {
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 98 60").unwrap();
+        Game::make_move(&mut game, Move::new(sq("g1"), sq("f3")), true).unwrap();
+        assert!(!Game::claim_draw(&mut game));
+        Game::make_move(&mut game, Move::new(sq("g8"), sq("f6")), true).unwrap();
+        assert!(Game::claim_draw(&mut game));
+        assert_eq!(game.state, GameState::GameOver(GameResult::Draw(DrawReason::FiftyMoveRule)));
+    }
+
+    #[test]
+    fn seventy_five_move_rule_ends_the_game() {
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 148 60").unwrap();
+        Game::make_move(&mut game, Move::new(sq("g1"), sq("f3")), true).unwrap();
+        assert_eq!(game.state, GameState::InProgress);
+        let state = Game::make_move(&mut game, Move::new(sq("g8"), sq("f6")), true).unwrap().state;
+        assert_eq!(state, GameState::GameOver(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+    }
+
+    #[test]
+    fn moves_can_be_made_in_check() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("f7"), sq("f6")), true).unwrap();
+        let state = Game::make_move(&mut game, Move::new(sq("d1"), sq("h5")), true).unwrap().state;
+        assert_eq!(state, GameState::Check);
+        let state = Game::make_move(&mut game, Move::new(sq("g7"), sq("g6")), true).unwrap().state;
+        assert_eq!(state, GameState::InProgress);
+        assert_eq!(game.color, Color::White);
+    }
+
+    #[test]
+    fn game_results() {
+        let mut game = Game::new();
+        assert_eq!(game.state.result_string(), "*");
+
+        Game::timeout(&mut game, Color::White);
+        assert_eq!(game.state, GameState::GameOver(GameResult::Timeout { winner: Color::Black }));
+        assert_eq!(game.state.result_string(), "0-1");
+
+        let checkmate = GameResult::Checkmate { winner: Color::White };
+        assert_eq!(checkmate.winner(), Some(Color::White));
+        assert_eq!(checkmate.result_string(), "1-0");
+        let draw = GameResult::Draw(DrawReason::Stalemate);
+        assert_eq!(draw.winner(), None);
+        assert_eq!(draw.result_string(), "1/2-1/2");
+    }
+
+    #[test]
+    fn resignation() {
+        let mut game = Game::new();
+        Game::resign(&mut game, Color::White);
+        assert_eq!(game.state, GameState::GameOver(GameResult::Resignation { winner: Color::Black }));
+        assert_eq!(game.state.result_string(), "0-1");
+
+        // The game is already decided, so it can't be resigned again or drawn
+        Game::resign(&mut game, Color::Black);
+        assert!(!Game::offer_draw(&mut game, Color::Black));
+        assert_eq!(game.state.result_string(), "0-1");
+    }
+
+    #[test]
+    fn draw_offer_accepted() {
+        let mut game = Game::new();
+        assert!(Game::offer_draw(&mut game, Color::White));
+        assert!(!Game::accept_draw(&mut game, Color::White));
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        assert!(Game::accept_draw(&mut game, Color::Black));
+        assert_eq!(game.state, GameState::GameOver(GameResult::Draw(DrawReason::Agreement)));
+        assert_eq!(game.state.result_string(), "1/2-1/2");
+    }
+
+    #[test]
+    fn draw_offer_declined() {
+        let mut game = Game::new();
+        Game::offer_draw(&mut game, Color::White);
+        assert!(Game::decline_draw(&mut game, Color::Black));
+        assert!(!Game::accept_draw(&mut game, Color::Black));
+        assert_eq!(game.state, GameState::InProgress);
+    }
+
+    #[test]
+    fn draw_offer_expires() {
+        let mut game = Game::new();
+        Game::offer_draw(&mut game, Color::White);
+        Game::make_move(&mut game, Move::new(sq("e2"), sq("e4")), true).unwrap();
+        Game::make_move(&mut game, Move::new(sq("e7"), sq("e5")), true).unwrap();
+        assert!(game.draw_offer.is_some());
+        Game::make_move(&mut game, Move::new(sq("g1"), sq("f3")), true).unwrap();
+        assert!(game.draw_offer.is_none());
+        assert!(!Game::accept_draw(&mut game, Color::Black));
+
+        // An offer made on the other color's turn goes away after the offering color's next move
+        Game::offer_draw(&mut game, Color::White);
+        Game::make_move(&mut game, Move::new(sq("b8"), sq("c6")), true).unwrap();
+        assert!(game.draw_offer.is_some());
+        Game::make_move(&mut game, Move::new(sq("f1"), sq("c4")), true).unwrap();
+        assert!(!Game::accept_draw(&mut game, Color::Black));
+    }
+
+    #[test]
+    fn fen_round_trips() {
+        assert_eq!(Game::to_fen(&Game::new()), START_FEN);
+        assert_eq!(Game::from_fen(START_FEN).unwrap().board(), Game::new().board());
+
+        let fixtures = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ];
+        for fen in fixtures.iter() {
+            assert_eq!(Game::to_fen(&Game::from_fen(fen).unwrap()), *fen);
+        }
+
+        // Without the clocks the halfmove clock is 0 and the fullmove number is 1
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!(Game::to_fen(&game), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn fen_after_moves() {
+        let mut game = Game::new();
+        Game::make_move(&mut game, "e2 e4".parse().unwrap(), true).unwrap();
+        assert_eq!(Game::to_fen(&game), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        Game::make_move(&mut game, "g8 f6".parse().unwrap(), true).unwrap();
+        assert_eq!(Game::to_fen(&game), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 2");
+
+        // A loaded position plays on like any other
+        let mut game = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3").unwrap();
+        Game::make_move(&mut game, "e5 f6".parse().unwrap(), true).unwrap();
+        assert_eq!(game.piece_at(sq("f5")), None);
+        Game::undo_move(&mut game);
+        assert_eq!(Game::to_fen(&game), "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+
+        // The largest move counters don't overflow when the next move is made
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 4294967295").unwrap();
+        Game::make_move(&mut game, "e8 d7".parse().unwrap(), true).unwrap();
+        assert_eq!(game.fullmove_number, u32::MAX);
+        game.halfmove_clock = u32::MAX;
+        Game::make_move(&mut game, "a1 a2".parse().unwrap(), true).unwrap();
+        assert_eq!(game.halfmove_clock, u32::MAX);
+    }
+
+    #[test]
+    fn fen_errors() {
+        let errors = [
+            ("8/8/8/8/8/8/8/8 w", FenError::WrongNumberOfFields(2)),
+            ("8/8/8/8/8/8/8/8 w - - 0", FenError::WrongNumberOfFields(5)),
+            ("8/8/8/8/8/8/8 w - - 0 1", FenError::WrongNumberOfRanks(7)),
+            ("4k3/8/8/8/8/8/8/4K2Z w - - 0 1", FenError::InvalidPiece { rank: 1, letter: 'Z' }),
+            ("4k3/8/8/8/8/8/8/4K2 w - - 0 1", FenError::WrongRankLength { rank: 1, length: 7 }),
+            ("4k4/8/8/8/8/8/8/4K3 w - - 0 1", FenError::WrongRankLength { rank: 8, length: 9 }),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidColor("x".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w KK - 0 1", FenError::InvalidCastling("KK".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w KX - 0 1", FenError::InvalidCastling("KX".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9 0 1", FenError::InvalidEnPassant("e9".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - e3 0 1", FenError::InvalidEnPassant("e3".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - -1 1", FenError::InvalidHalfmoveClock("-1".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenError::InvalidFullmoveNumber("0".to_string())),
+        ];
+        for (fen, error) in errors.iter() {
+            assert_eq!(Game::from_fen(fen).err().as_ref(), Some(error), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn fen_positions_that_are_already_over() {
+        let game = Game::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(game.state, GameState::GameOver(GameResult::Checkmate { winner: Color::Black }));
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.state, GameState::GameOver(GameResult::Draw(DrawReason::Stalemate)));
+    }
+
+    #[test]
+    fn san_of_moves() {
+        let san = |fen: &str, mv: &str| Game::to_san(&Game::from_fen(fen).unwrap(), mv.parse().unwrap());
+        assert_eq!(san(START_FEN, "g1f3").unwrap(), "Nf3");
+        assert_eq!(san(START_FEN, "e2e4").unwrap(), "e4");
+        assert_eq!(san(START_FEN, "e2e5"), Err(MoveError::IllegalMove));
+
+        // Captures, en passant, promotions and castling
+        let fen = "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+        assert_eq!(san(fen, "e5d6").unwrap(), "exd6");
+        assert_eq!(san(fen, "b7b8n").unwrap(), "b8=N");
+        assert_eq!(san(fen, "b7a8q").unwrap(), "bxa8=Q+");
+        assert_eq!(san(fen, "e1g1").unwrap(), "O-O");
+        assert_eq!(san(fen, "e1c1").unwrap(), "O-O-O");
+        assert_eq!(san(fen, "a1a8").unwrap(), "Rxa8+");
+
+        // The file tells the knights apart, then the rank, and for three queens both
+        let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san(fen, "b1d2").unwrap(), "Nbd2");
+        let fen = "4k3/8/8/8/8/8/8/RN2K1NR w - - 0 1";
+        assert_eq!(san(fen, "g1f3").unwrap(), "Nf3");
+        let fen = "4k3/1N6/8/8/8/8/8/1N2K3 w - - 0 1";
+        assert_eq!(san(fen, "b1c3").unwrap(), "Nc3");
+        let fen = "4k3/8/8/1N6/8/8/8/1N2K3 w - - 0 1";
+        assert_eq!(san(fen, "b1a3").unwrap(), "N1a3");
+        let fen = "8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(san(fen, "h4e1").unwrap(), "Qh4e1");
+        assert_eq!(san(fen, "h4d8").unwrap(), "Qd8+");
+
+        // A knight that can't move because it's pinned doesn't need to be told apart
+        let fen = "4r2k/8/8/8/8/8/N3N3/4K3 w - - 0 1";
+        assert_eq!(san(fen, "a2c3").unwrap(), "Nc3");
+
+        // Fool's mate
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2";
+        assert_eq!(san(fen, "d8h4").unwrap(), "Qh4#");
+    }
+
+    #[test]
+    fn san_is_read_as_legal_moves() {
+        let game = Game::new();
+        assert_eq!(Game::parse_san(&game, "Nf3"), Ok(Move::new(sq("g1"), sq("f3"))));
+        assert_eq!(Game::parse_san(&game, "e4").map(|mv| mv.flags.double_pawn_push), Ok(true));
+        assert_eq!(Game::parse_san(&game, "Nd2"), Err(SanError::IllegalMove("Nd2".to_string())));
+        assert_eq!(Game::parse_san(&game, "Zf3"), Err(SanError::BadNotation("Zf3".to_string())));
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(Game::parse_san(&game, "Nd2"), Err(SanError::AmbiguousMove("Nd2".to_string())));
+        assert_eq!(Game::parse_san(&game, "Nfd2"), Ok(Move::new(sq("f1"), sq("d2"))));
+    }
+
+    // Makes moves written as two squares, e.g. "e2 e4", one after another
+    fn play(game: &mut Game, moves: &[&str]) {
+        for mv in moves {
+            Game::make_move(game, mv.parse().unwrap(), true).unwrap();
+        }
+    }
+
+    #[test]
+    fn pgn_of_a_game() {
+        let mut game = Game::new();
+        play(&mut game, &["f2 f3", "e7 e5", "g2 g4", "d8 h4"]);
+        let tags = PgnTags {
+            event: Some("Club \"Blitz\"".to_string()),
+            white: Some("Fool".to_string()),
+            custom: vec![("TimeControl".to_string(), "180+2".to_string())],
+            ..PgnTags::default()
+        };
+        let annotations = vec![
+            PgnAnnotation::default(),
+            PgnAnnotation { comment: None, clock: Some(Duration::from_secs(178)) },
+            PgnAnnotation { comment: Some("The worst move".to_string()), clock: Some(Duration::from_secs(3725)) },
+        ];
+        assert_eq!(
+            Game::to_pgn(&game, &tags, &annotations),
+            "[Event \"Club \\\"Blitz\\\"\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Fool\"]\n\
+             [Black \"?\"]\n\
+             [Result \"0-1\"]\n\
+             [TimeControl \"180+2\"]\n\
+             \n\
+             1. f3 e5 {[%clk 0:02:58]} 2. g4 {[%clk 1:02:05] The worst move} 2... Qh4# 0-1\n"
+        );
+
+        // Moves that were taken back aren't written
+        Game::undo_move(&mut game);
+        assert_eq!(Game::to_pgn(&game, &PgnTags::default(), &[]).split("\n\n").nth(1), Some("1. f3 e5 2. g4 *\n"));
+    }
+
+    #[test]
+    fn pgn_from_a_position() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/R7/4K3 b - - 0 40").unwrap();
+        play(&mut game, &["e8 d8", "a2 a8"]);
+        let pgn = Game::to_pgn(&game, &PgnTags::default(), &[]);
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/R7/4K3 b - - 0 40\"]\n"));
+        assert!(pgn.ends_with("\n\n40... Kd8 41. Ra8+ *\n"));
+
+        // Long games are broken into lines of at most 80 characters
         let mut game = Game::new();
+        play(&mut game, &["e2 e4", "e7 e5", "g1 f3", "b8 c6", "f1 b5", "a7 a6", "b5 a4", "g8 f6", "e1 g1", "f8 e7"]);
+        play(&mut game, &["f1 e1", "b7 b5", "a4 b3", "d7 d6", "c2 c3", "e8 g8", "h2 h3", "c6 b8", "d2 d4", "b8 d7"]);
+        let pgn = Game::to_pgn(&game, &PgnTags::default(), &[]);
+        let movetext: Vec<&str> = pgn.split("\n\n").nth(1).unwrap().lines().collect();
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= 80));
+        assert_eq!(movetext.join(" ").split(' ').count(), 20 + 10 + 1);
+        assert!(movetext[0].starts_with("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3"));
+    }
+
+    #[test]
+    fn san_round_trips_for_every_legal_move() {
+        let fixtures = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "k7/8/8/8/Q6Q/8/8/Q3K3 w - - 0 1",
+        ];
+        for fen in fixtures.iter() {
+            let game = Game::from_fen(fen).unwrap();
+            let moves = Game::get_all_possible_moves(&game, game.color);
+            let sans: Vec<String> = moves.iter().map(|mv| Game::to_san(&game, *mv).unwrap()).collect();
+            for (mv, san) in moves.iter().zip(sans.iter()) {
+                assert_eq!(Game::parse_san(&game, san), Ok(*mv), "{} in {}", san, fen);
+                assert_eq!(sans.iter().filter(|other| *other == san).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn san_is_read_leniently() {
+        let game = Game::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let read = |san: &str| Game::parse_san(&game, san).unwrap();
+        let bxa8 = read("bxa8=Q+");
+        assert_eq!((bxa8.from, bxa8.to, bxa8.promotion), (sq("b7"), sq("a8"), Some(PieceType::Queen)));
+        assert_eq!(read("bxa8Q"), bxa8);
+        assert_eq!(read("bxa8=N").promotion, Some(PieceType::Knight));
+        assert!(read("exd6!?").flags.en_passant);
+        assert_eq!(read("0-0"), read("O-O"));
+        assert_eq!(read("O-O-O").to, sq("c1"));
+        assert_eq!(read("Ra1xa8+").to, sq("a8"));
+
+        // A capture that isn't one, and a pawn that has to be promoted
+        assert_eq!(Game::parse_san(&game, "Rxa7"), Err(SanError::IllegalMove("Rxa7".to_string())));
+        assert_eq!(Game::parse_san(&game, "b8"), Err(SanError::IllegalMove("b8".to_string())));
+        assert_eq!(Game::parse_san(&game, "b8=K"), Err(SanError::IllegalMove("b8=K".to_string())));
+        assert_eq!(Game::parse_san(&game, "O-O-O-O"), Err(SanError::BadNotation("O-O-O-O".to_string())));
+        assert_eq!(Game::parse_san(&game, "x"), Err(SanError::BadNotation("x".to_string())));
+    }
+
+    #[test]
+    fn uci_moves() {
+        let game = Game::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+        let read = |uci: &str| Game::parse_uci(&game, uci);
+        assert_eq!(read("0000"), Ok(None));
+        assert!(read("e1g1").unwrap().unwrap().flags.castling);
+        assert!(read("e5d6").unwrap().unwrap().flags.en_passant);
+        assert_eq!(read("b7a8n").unwrap().unwrap().promotion, Some(PieceType::Knight));
+
+        assert_eq!(read("b7b8"), Err(MoveError::InvalidPromotion));
+        assert_eq!(read("e1e3"), Err(MoveError::IllegalMove));
+        assert_eq!(read("d5d4"), Err(MoveError::WrongSide(Color::White)));
+        assert_eq!(read("c3c4"), Err(MoveError::EmptySquare(sq("c3"))));
+        for bad in ["e2 e4", "E1G1", "b7a8Q", "e1", "e1g1qq", "i1g1", ""].iter() {
+            assert_eq!(read(bad), Err(MoveError::BadNotation(bad.to_string())));
+        }
+
+        // Every legal move is written in a way that reads back as the same move
+        for mv in Game::get_all_possible_moves(&game, Color::White) {
+            let uci = Game::to_uci(&game, Some(mv)).unwrap();
+            assert_eq!(uci, mv.to_string());
+            assert_eq!(read(&uci), Ok(Some(mv)));
+        }
+        assert_eq!(Game::to_uci(&game, Some(Move::with_promotion(sq("b7"), sq("b8"), PieceType::Queen))).unwrap(), "b7b8q");
+        assert_eq!(Game::to_uci(&game, None).unwrap(), NULL_MOVE_UCI);
+        assert_eq!(Game::to_uci(&game, Some(Move::new(sq("a1"), sq("b2")))), Err(MoveError::IllegalMove));
+    }
+
+    const EPD_SUITE: &str = r#"4k3/8/8/3q4/8/8/3R4/4K3 w - - bm Rxd5; id "free queen";
+2r3k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8+; am Rc1 Rd2; c0 "Trade, then push; carefully"; id "back rank";
+
+4k3/8/8/8/8/8/8/R3K3 w Q - ce 500; acd 3; id "no test";
+"#;
+
+    #[test]
+    fn epd_records() {
+        let records = EpdRecord::read_all(EPD_SUITE).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let back_rank = &records[1];
+        assert_eq!(back_rank.fen, "2r3k1/5ppp/8/8/8/8/5PPP/3R2K1 w - -");
+        assert_eq!(back_rank.id.as_deref(), Some("back rank"));
+        assert_eq!(back_rank.best_moves.iter().map(|mv| (mv.from, mv.to)).collect::<Vec<_>>(), vec![(sq("d1"), sq("d8"))]);
+        assert_eq!(back_rank.avoid_moves.len(), 2);
+        assert_eq!(back_rank.operations, vec![("c0".to_string(), "\"Trade, then push; carefully\"".to_string())]);
+        assert_eq!(records[2].centipawns, Some(500));
+        assert_eq!(records[2].depth, Some(3));
+        assert_eq!(Game::to_fen(&records[2].to_game().unwrap()), "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+
+        // Records are written back with the id last
+        assert_eq!(
+            back_rank.to_string(),
+            "2r3k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8+; am Rc1 Rd2; c0 \"Trade, then push; carefully\"; id \"back rank\";"
+        );
+        assert_eq!(records[2].to_string(), "4k3/8/8/8/8/8/8/R3K3 w Q - ce 500; acd 3; id \"no test\";");
+        for record in records.iter() {
+            assert_eq!(record.to_string().parse::<EpdRecord>().as_ref(), Ok(record));
+        }
+    }
+
+    #[test]
+    fn epd_errors() {
+        let error = |epd: &str| epd.parse::<EpdRecord>().unwrap_err();
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w -"), EpdError::BadFen(FenError::WrongNumberOfFields(3)));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - id \"open;"), EpdError::BadOperation("id \"open;".to_string()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - ce lots;"), EpdError::BadOperation("ce lots".to_string()));
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - bm Kd1 Ke3;"),
+            EpdError::BadMove { opcode: "bm".to_string(), error: SanError::IllegalMove("Ke3".to_string()) }
+        );
+    }
+
+    #[test]
+    fn epd_suite_is_run_with_the_ai() {
+        let records = EpdRecord::read_all(EPD_SUITE).unwrap();
+        let report = Game::run_epd_suite(&records);
+
+        // The record without bm or am isn't part of the report
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.results[0].0.as_deref(), Some("free queen"));
+        let free_queen = report.results[0].1.unwrap();
+        assert_eq!((free_queen.from, free_queen.to), (sq("d2"), sq("d5")));
+        assert!(report.results[0].2);
+        assert_eq!(report.solved_percentage(), 100.0 * report.solved() as f64 / 2.0);
+    }
+
+    const PGN_GAMES: &str = r#"[Event "Casual game"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+[Opening "Philidor \"Defence\""]
+
+% Lines starting with a percent sign are skipped
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 $2 {This is a weak move
+already.} ( 3... exd4 4. Nxd4 (4. Qxd4 Nc6) 4... Nf6 ) 4. dxe5 Bxf3 5. Qxf3 dxe5
+6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5?! 10. Nxb5! cxb5 11. Bxb5+ Nbd7
+12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+
+[Event "?"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/R7/4K3 b - - 0 40"]
+
+{Starting from an ending} 40... Kd8 {[%clk 0:01:05]} 41. Ra8+ ; the rook checks
+*
+"#;
+
+    #[test]
+    fn pgn_games_are_read() {
+        let games = Game::from_pgn(PGN_GAMES).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let opera = &games[0];
+        assert_eq!(opera.tags.white.as_deref(), Some("Paul Morphy"));
+        assert_eq!(opera.tags.custom, vec![("Opening".to_string(), "Philidor \"Defence\"".to_string())]);
+        assert_eq!(opera.result, "1-0");
+        assert_eq!(opera.moves.len(), 33);
+        assert_eq!(opera.game.state, GameState::GameOver(GameResult::Checkmate { winner: Color::White }));
+        assert_eq!(Game::moves(&opera.game).len(), 33);
+
+        let bg4 = &opera.moves[5];
+        assert_eq!(bg4.san, "Bg4");
+        assert_eq!(bg4.nags, vec![2]);
+        assert_eq!(bg4.comments, vec!["This is a weak move already.".to_string()]);
+        assert_eq!(opera.moves[17].nags, vec![6]);
+        assert_eq!(opera.moves[18].nags, vec![1]);
+
+        // The variation replaces 3... Bg4, and has a variation of its own for 4. Nxd4
+        assert_eq!(bg4.variations.len(), 1);
+        let variation = &bg4.variations[0];
+        let sans: Vec<&str> = variation.iter().map(|pgn_move| pgn_move.san.as_str()).collect();
+        assert_eq!(sans, vec!["exd4", "Nxd4", "Nf6"]);
+        let sans: Vec<&str> = variation[1].variations[0].iter().map(|pgn_move| pgn_move.san.as_str()).collect();
+        assert_eq!(sans, vec!["Qxd4", "Nc6"]);
+        let qxd4 = variation[1].variations[0][0].mv;
+        assert_eq!((qxd4.from, qxd4.to, qxd4.flags.capture), (sq("d1"), sq("d4"), true));
+
+        let ending = &games[1];
+        assert_eq!(ending.result, "*");
+        assert_eq!(ending.tags, PgnTags::default());
+        assert_eq!(ending.moves[0].comments_before, vec!["Starting from an ending".to_string()]);
+        assert_eq!(ending.moves[0].clock, Some(Duration::from_secs(65)));
+        assert_eq!(ending.moves[0].comments, Vec::<String>::new());
+        assert_eq!(ending.moves[1].comments, vec!["the rook checks".to_string()]);
+        assert_eq!(Game::to_fen(&ending.game), "R2k4/8/8/8/8/8/8/4K3 b - - 2 41");
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let games = Game::from_pgn(PGN_GAMES).unwrap();
+        let ending = &games[1];
+        let pgn = Game::to_pgn(&ending.game, &ending.tags, &ending.annotations());
+        assert!(pgn.ends_with("\n40... Kd8 {[%clk 0:01:05]} 41. Ra8+ {the rook checks} *\n"));
+
+        let read_again = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_again.len(), 1);
+        assert_eq!(read_again[0].tags, ending.tags);
+        assert_eq!(read_again[0].moves, ending.moves.iter().map(|pgn_move| {
+            let mut pgn_move = pgn_move.clone();
+            pgn_move.comments_before.clear();
+            pgn_move
+        }).collect::<Vec<_>>());
+        assert_eq!(Game::to_pgn(&read_again[0].game, &read_again[0].tags, &read_again[0].annotations()), pgn);
+    }
+
+    #[test]
+    fn pgn_errors() {
+        let error = |pgn: &str| Game::from_pgn(pgn).unwrap_err();
+
+        // The second game has an illegal move for black at the fourth ply
+        let pgn = "1. e4 e5 2. Nf3 Nc6 1-0\n\n[Event \"?\"]\n\n1. d4 d5 2. c4 Qd5 *";
+        assert_eq!(
+            error(pgn),
+            PgnError { game: 2, ply: 4, kind: PgnErrorKind::BadMove(SanError::IllegalMove("Qd5".to_string())) }
+        );
+        assert_eq!(error(pgn).to_string(), "Game 2, ply 4: Qd5 isn't a legal move");
+
+        let pgn = "[FEN \"4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1\"]\n\n1. Nd2 *";
+        assert_eq!(error(pgn).kind, PgnErrorKind::BadMove(SanError::AmbiguousMove("Nd2".to_string())));
+        assert_eq!(error("1. e4 (1. d4 d5 2. Qd8 *").ply, 3);
+        assert_eq!(error("1. e4 e5 (2. Nf3) *").kind, PgnErrorKind::BadMove(SanError::IllegalMove("Nf3".to_string())));
+        assert_eq!(error("1. e4 e5 (1... c5 *").kind, PgnErrorKind::BadVariation);
+        assert_eq!(error("1. e4 e5) *").kind, PgnErrorKind::BadVariation);
+        assert_eq!(error("(1. d4) 1. e4 *").kind, PgnErrorKind::BadVariation);
+        assert_eq!(error("1. e4 {never closed").kind, PgnErrorKind::BadSyntax("a comment that is never closed".to_string()));
+        assert_eq!(error("[FEN \"8/8 w - - 0 1\"] *").kind, PgnErrorKind::BadFen(FenError::WrongNumberOfRanks(2)));
+        assert_eq!(error("1. f3 e5 2. g4 Qh4# 3. e4 0-1").ply, 5);
+
+        // A game from a FEN counts on from its move number, with black's moves on even plies
+        let pgn = "[FEN \"4k3/8/8/8/8/8/R7/4K3 b - - 0 40\"]\n\n40... Kd8 41. Rb8 *";
+        assert_eq!(error(pgn).ply, 81);
+        assert_eq!(error(pgn).to_string(), "Game 1, ply 81: Rb8 isn't a legal move");
+        let pgn = "[FEN \"4k3/8/8/8/8/8/R7/4K3 b - - 0 40\"]\n\n40... Kd9 *";
+        assert_eq!(error(pgn).ply, 80);
+        assert!(Game::from_pgn("  \n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn pgn_clocks_that_cant_be_read() {
+        // A clock that isn't a time a game can have is left in the comment instead
+        for clock in ["inf", "NaN", "-1:00", "99999999999999999999:00:00", "1e300"] {
+            let pgn = format!("1. e4 {{[%clk {}]}} *", clock);
+            let games = Game::from_pgn(&pgn).unwrap();
+            assert_eq!(games[0].moves[0].clock, None);
+            assert_eq!(games[0].moves[0].comments, vec![format!("[%clk {}]", clock)]);
+        }
+        let games = Game::from_pgn("1. e4 {[%clk 0:00:05.5]} *").unwrap();
+        assert_eq!(games[0].moves[0].clock, Some(Duration::from_millis(5500)));
+    }
+
+    // Returns the squares of a bitboard, written like "e4"
+    fn bitboard_squares(bitboard: u64) -> Vec<String> {
+        crate::bitboard::squares(bitboard).map(|square| square.to_string()).collect()
+    }
+
+    #[test]
+    fn bitboards() {
+        let game = Game::new();
+        let bitboards = *game.bitboards();
+        assert_eq!(Bitboards::from_board(&game.board()), bitboards);
+        assert_eq!(bitboards.to_board(), game.board());
+        assert_eq!(bitboards.occupied().count_ones(), 32);
+        assert_eq!(bitboards.color(Color::White), 0xffff);
+        assert_eq!(bitboards.pieces(PieceType::Pawn, Color::Black), 0xff << 48);
+        assert_eq!(bitboards.king_square(Color::Black), Some(sq("e8")));
+        assert_eq!(bitboard_squares(bitboards.attacks(sq("g1"))), ["e2", "f3", "h3"]);
+        assert_eq!(bitboard_squares(bitboards.attacks(sq("d2"))), ["c3", "e3"]);
+        assert_eq!(bitboards.attacks(sq("a1")), 0x102);
+        assert_eq!(bitboards.attacks(sq("e4")), 0);
+
+        // Changing the board and putting it back is what assigning to the board field was
+        let mut game = Game::new();
+        let mut board = game.board();
+        board[4][4] = board[6][4].take();
+        game.set_board(board);
+        assert_eq!(game.board(), board);
+        assert_eq!(game.piece_at(sq("e2")), None);
+        assert_eq!(game.piece_at(sq("e4")), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }));
+
+        // Sliding pieces stop at the first piece in the way, whichever color it is
+        let game = Game::from_fen("4k3/8/1p6/8/3Q1P2/8/1P6/4K3 w - - 0 1").unwrap();
+        let queen = game.bitboards().attacks(sq("d4"));
+        assert_eq!(queen.count_ones(), 23);
+        assert_eq!(bitboard_squares(queen & game.bitboards().occupied()), ["b2", "f4", "b6"]);
+        assert_eq!(bitboard_squares(game.bitboards().attackers(sq("c3"), Color::White)), ["b2", "d4"]);
+        assert_eq!(bitboard_squares(game.bitboards().attackers(sq("c5"), Color::Black)), ["b6"]);
+        assert!(!game.bitboards().is_attacked(sq("h4"), Color::White));
+
+        // Castling and en passant move the extra piece as well
+        let mut bitboards = Bitboards::empty();
+        bitboards.set_piece(sq("e1"), Some(Piece { piecetype: PieceType::King, color: Color::White }));
+        bitboards.set_piece(sq("h1"), Some(Piece { piecetype: PieceType::Rook, color: Color::White }));
+        let mut castling = Move::new(sq("e1"), sq("g1"));
+        castling.flags.castling = true;
+        bitboards.make_move(castling);
+        assert_eq!(bitboard_squares(bitboards.occupied()), ["f1", "g1"]);
+        assert_eq!(bitboards.piece_at(sq("f1")).map(|piece| piece.piecetype), Some(PieceType::Rook));
+    }
+
+    /* Well-known positions for testing move generation, with the number of move sequences
+    of each length from 1 up, as published on the Chess Programming Wiki. The longer
+    counts are left out to keep the tests quick */
+    const PERFT_POSITIONS: [(&str, &[u64]); 6] = [
+        (START_FEN, &[20, 400, 8902, 197281]),
+        // "Kiwipete", with castling, en passant and promotions all over
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238]),
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467]),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379]),
+        ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890]),
+    ];
+
+    #[test]
+    fn perft_positions() {
+        for (fen, counts) in PERFT_POSITIONS.iter() {
+            let game = Game::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(Game::perft(&game, depth as u32 + 1), *count, "{} at depth {}", fen, depth + 1);
+            }
+            assert_eq!(Game::perft(&game, 0), 1);
+        }
+    }
+
+    #[test]
+    fn perft_divide() {
+        let mut game = Game::new();
+        play(&mut game, &["e2 e4", "d7 d5"]);
+        let before = Game::to_fen(&game);
+        let divided = Game::perft_divide(&game, 2);
+        assert_eq!(divided.len(), 31);
+        assert_eq!(divided.iter().map(|(_, leaves)| leaves).sum::<u64>(), Game::perft(&game, 2));
+        let capture = divided.iter().find(|(mv, _)| mv.to_string() == "e4d5").unwrap();
+        assert!(capture.0.flags.capture);
+        // After exd5 black has 14 pawn moves, 5 knight moves, 5 bishop moves, Qd7, Qd6, Qxd5 and Kd7
+        assert_eq!(capture.1, 28);
+        assert!(Game::perft_divide(&game, 0).is_empty());
+        assert_eq!(Game::to_fen(&game), before);
+    }
+
+    #[test]
+    fn zobrist_keys_follow_moves_and_undos() {
+        // Plays through the moves in a fixed but mixed up order, which gets to captures,
+        // castling, en passant and promotions, checking the key against one made from nothing
+        let mut game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut keys = vec![Game::zobrist_key(&game)];
+        for ply in 0..60 {
+            let moves = Game::get_all_possible_moves(&game, game.color);
+            if moves.is_empty() || game.state.is_game_over() {
+                break;
+            }
+            Game::make_move(&mut game, moves[(ply * 7) % moves.len()], true).unwrap();
+            assert_eq!(Game::zobrist_key(&game), Game::compute_zobrist_key(&game));
+            keys.push(Game::zobrist_key(&game));
+        }
+        while Game::undo_move(&mut game).is_some() {
+            keys.pop();
+            assert_eq!(Game::zobrist_key(&game), Game::compute_zobrist_key(&game));
+            assert_eq!(Game::zobrist_key(&game), *keys.last().unwrap());
+        }
+    }
+
+    #[test]
+    fn zobrist_keys_kept_by_the_game() {
+        let check = |game: &Game| assert_eq!(Game::zobrist_key(game), Game::compute_zobrist_key(game));
+        let mut game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+        let key = Game::zobrist_key(&game);
+
+        // Putting a piece next to the pawn that passed changes the en passant file as well
+        Game::set_piece(&mut game, sq("e5"), None);
+        check(&game);
+        Game::set_piece(&mut game, sq("c5"), Some(Piece { piecetype: PieceType::Pawn, color: Color::White }));
+        check(&game);
+        game.set_board(Game::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap().board());
+        assert_eq!(Game::zobrist_key(&game), key);
+
+        // A move that doesn't change the color, and the AI, which changes it to look ahead
+        Game::make_move(&mut game, Move::new(sq("h1"), sq("h2")), false).unwrap();
+        check(&game);
+        Game::undo_move(&mut game);
+        assert!(Game::ai_best_move(&mut game).is_some());
+        assert_eq!(Game::zobrist_key(&game), key);
+
+        // The fields can be set directly, and then the key has to be worked out again
+        game.castling_rights = CastlingRights::all();
+        assert_eq!(Game::zobrist_key(&game), key);
+        Game::refresh_zobrist_key(&mut game);
+        check(&game);
+        assert_ne!(Game::zobrist_key(&game), key);
+    }
+
+    #[test]
+    fn zobrist_keys_of_positions() {
+        // The same position reached in a different order has the same key
+        let mut first = Game::new();
+        play(&mut first, &["g1 f3", "g8 f6", "b1 c3"]);
+        let mut second = Game::new();
+        play(&mut second, &["b1 c3", "g8 f6", "g1 f3"]);
+        assert_eq!(Game::zobrist_key(&first), Game::zobrist_key(&second));
+        assert_eq!(Game::get_position(&first).key, Game::zobrist_key(&first));
+
+        // The color to move, the castling rights and en passant all count
+        let key = |fen: &str| Game::zobrist_key(&Game::from_fen(fen).unwrap());
+        let start = key(START_FEN);
+        assert_eq!(start, Game::compute_zobrist_key(&Game::new()));
+        assert_ne!(start, key("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"));
+        assert_ne!(start, key("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1"));
+        assert_ne!(
+            key("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"),
+            key("4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1")
+        );
+        // An en passant square no pawn can capture on doesn't make a different position
+        assert_eq!(
+            key("4k3/8/8/3p4/8/8/8/4K3 w - d6 0 1"),
+            key("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1")
+        );
+        // The clocks don't count
+        assert_eq!(start, key("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 30 40"));
+    }
+
+    // Returns the squares written like "e4", or None if there are none to give
+    fn square_names(squares: Option<Vec<Square>>) -> Option<Vec<String>> {
+        squares.map(|squares| squares.iter().map(|square| square.to_string()).collect())
+    }
+
+    #[test]
+    fn attacks_and_pins() {
+        let game = Game::from_fen("4r2k/8/8/8/1b2N2q/8/3B1P2/4K1Nb w - - 0 1").unwrap();
+        assert!(Game::is_square_attacked(&game, sq("e2"), Color::White));
+        assert!(!Game::is_square_attacked(&game, sq("e1"), Color::Black));
+        let attackers = Game::attackers_of(&game, sq("e4"), Color::Black);
+        assert_eq!(attackers, [sq("h1"), sq("h4"), sq("e8")]);
+        assert!(Game::attackers_of(&game, sq("a8"), Color::White).is_empty());
+        assert_eq!(square_names(Game::checkers(&game)), Some(vec![]));
+
+        // The knight on g1 is in front of a bishop, which doesn't slide along the rank
+        let pinned = square_names(Game::pinned_pieces(&game, Color::White));
+        assert_eq!(pinned, Some(vec!["d2".to_string(), "f2".to_string(), "e4".to_string()]));
+        assert_eq!(square_names(Game::pinned_pieces(&game, Color::Black)), Some(vec![]));
+
+        // Double check, by a rook and a knight
+        let game = Game::from_fen("4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1").unwrap();
+        let checkers = square_names(Game::checkers(&game));
+        assert_eq!(checkers, Some(vec!["a1".to_string(), "f3".to_string()]));
+    }
+
+    #[test]
+    fn positions_without_a_king() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R7 w - - 0 1").unwrap();
+        assert_eq!(Game::checkers(&game), None);
+        assert_eq!(Game::pinned_pieces(&game, Color::White), None);
+        assert!(!Game::check_check(&mut game));
+        assert_eq!(Game::attackers_of(&game, sq("a8"), Color::White), [sq("a1")]);
+
+        // The color with a king can still be checked by one without
+        let game = Game::from_fen("8/8/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+        assert_eq!(Game::checkers(&game), Some(vec![sq("h1")]));
+        assert_eq!(Game::pinned_pieces(&game, Color::Black), None);
+    }
+
+    #[test]
+    fn test_print() {
+        let game = Game::new();
         Game::print(&game);
         println!("Does it look right?");
     }
@@ -0,0 +1,36 @@
+use crate::Game;
+use crate::Move;
+use crate::MoveError;
+
+// How UCI writes the null move, which engines send when they have no move to give
+pub const NULL_MOVE_UCI: &str = "0000";
+
+impl Game {
+    /* Reads a move in UCI long algebraic notation, e.g. "e2e4", "e1g1" for castling or
+    "e7e8q", and returns the legal move with its flags filled in, or None for the null
+    move "0000". The errors are the ones make_move would give for the move */
+    pub fn parse_uci(&self, uci: &str) -> Result<Option<Move>, MoveError> {
+        if uci == NULL_MOVE_UCI {
+            return Ok(None);
+        }
+
+        // UCI is stricter than Move::from_str, and only has lowercase letters without spaces
+        let written_like_uci = (uci.len() == 4 || uci.len() == 5)
+            && uci.chars().all(|letter| letter.is_ascii_lowercase() || letter.is_ascii_digit());
+        if !written_like_uci {
+            return Err(MoveError::BadNotation(uci.to_string()));
+        }
+        let mv: Move = uci.parse()?;
+        let mut after = self.clone();
+        Game::make_move(&mut after, mv, true).map(|outcome| Some(outcome.mv))
+    }
+
+    /* Writes a move of the current color in UCI long algebraic notation, or "0000" for
+    None, and returns why the move can't be made if it isn't legal */
+    pub fn to_uci(&self, mv: Option<Move>) -> Result<String, MoveError> {
+        match mv {
+            Some(mv) => Game::parse_uci(self, &mv.to_string()).map(|_| mv.to_string()),
+            None => Ok(NULL_MOVE_UCI.to_string()),
+        }
+    }
+}
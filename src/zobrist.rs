@@ -0,0 +1,143 @@
+use crate::bitboard;
+use crate::CastlingRights;
+use crate::Color;
+use crate::Game;
+use crate::Piece;
+use crate::Square;
+
+/* The random numbers that Zobrist keys are made of, one for every piece on every square,
+one for black to move, one for every castling right and one for every file an en passant
+capture can be made on. A key is all the numbers for a position XORed together, so a move
+changes it by XORing out what it takes away and XORing in what it adds */
+struct Keys {
+    pieces: [[[u64; 64]; 2]; 7],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant_files: [u64; 8],
+}
+
+const KEYS: Keys = Keys::generate();
+
+impl Keys {
+    /* Makes the numbers at compile time with SplitMix64 from a fixed seed, so that keys
+    are the same in every build and can be stored */
+    const fn generate() -> Keys {
+        let mut state: u64 = 0x5eed_c4e5_5b0a_4d01;
+        let mut keys = Keys {
+            pieces: [[[0; 64]; 2]; 7],
+            black_to_move: 0,
+            castling: [0; 4],
+            en_passant_files: [0; 8],
+        };
+        let mut piecetype = 0;
+        while piecetype < 7 {
+            let mut color = 0;
+            while color < 2 {
+                let mut square = 0;
+                while square < 64 {
+                    state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    keys.pieces[piecetype][color][square] = mix(state);
+                    square += 1;
+                }
+                color += 1;
+            }
+            piecetype += 1;
+        }
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        keys.black_to_move = mix(state);
+        let mut right = 0;
+        while right < 4 {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            keys.castling[right] = mix(state);
+            right += 1;
+        }
+        let mut file = 0;
+        while file < 8 {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            keys.en_passant_files[file] = mix(state);
+            file += 1;
+        }
+        keys
+    }
+}
+
+const fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// The number for a piece standing on a square
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    let color = match piece.color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+    KEYS.pieces[piece.piecetype as usize][color][square.index()]
+}
+
+// The numbers for the color to move, the castling rights and the en passant file
+fn state_key(color: Color, castling_rights: CastlingRights, en_passant: Option<Square>) -> u64 {
+    let mut key = 0;
+    if color == Color::Black {
+        key ^= KEYS.black_to_move;
+    }
+    let rights = [
+        castling_rights.white_kingside,
+        castling_rights.white_queenside,
+        castling_rights.black_kingside,
+        castling_rights.black_queenside,
+    ];
+    for (right, number) in rights.iter().zip(KEYS.castling.iter()) {
+        if *right {
+            key ^= number;
+        }
+    }
+    if let Some(target) = en_passant {
+        key ^= KEYS.en_passant_files[target.file() as usize];
+    }
+    key
+}
+
+impl Game {
+    /* Returns the Zobrist key of the current position, a 64-bit number that is the same
+    whenever the pieces, the color to move, the castling rights and the en passant file
+    are, and almost never the same otherwise. The game keeps the key and every move
+    changes it by what it changes, and an undo puts back the key from before the move.
+    Like for repetitions, the en passant file only counts if a pawn stands ready to capture */
+    pub fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    /* Works the key out from nothing by going over every piece on the board, which
+    is slower but shows if the key kept by the moves has gone wrong */
+    pub fn compute_zobrist_key(&self) -> u64 {
+        let en_passant = Game::capturable_en_passant(self);
+        let mut key = state_key(self.color, self.castling_rights, en_passant);
+        for square in bitboard::squares(self.bitboards.occupied()) {
+            key ^= piece_key(self.piece_at(square).unwrap(), square);
+        }
+        key
+    }
+
+    /* Works the key out from nothing and keeps it, which has to be done after the color,
+    the castling rights or the en passant square are set without going through a move */
+    pub fn refresh_zobrist_key(&mut self) {
+        self.key = Game::compute_zobrist_key(self);
+    }
+
+    /* Makes a change to the game and updates the key with it, by XORing out the numbers
+    for the pieces, the color, the castling rights and the en passant file from before the
+    change and XORing in those from after it. The numbers for what didn't change cancel out */
+    pub(crate) fn change_keyed(&mut self, change: impl FnOnce(&mut Game)) {
+        let before = self.bitboards.key() ^ Game::state_zobrist_key(self);
+        change(self);
+        self.key ^= before ^ self.bitboards.key() ^ Game::state_zobrist_key(self);
+    }
+
+    // The numbers for the color to move, the castling rights and the en passant file
+    fn state_zobrist_key(&self) -> u64 {
+        let en_passant = Game::capturable_en_passant(self);
+        state_key(self.color, self.castling_rights, en_passant)
+    }
+}